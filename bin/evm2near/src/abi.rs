@@ -1,12 +1,51 @@
 // This is free and unencumbered software released into the public domain.
 
+use evm_rs::Opcode::{PUSHn, PUSH1};
+
+use crate::opcode::{Opcode, Program};
 use serde::Deserialize;
 use sha3::{Digest, Keccak256};
-use std::fmt;
+use std::{collections::HashSet, fmt};
 
+/// The functions of a contract ABI, leaving out its constructor, events and errors.
 #[derive(Deserialize, Debug, PartialEq, Eq, Default)]
+#[serde(try_from = "Vec<serde_json::Value>")]
 pub struct Functions(Vec<Function>);
 
+impl TryFrom<Vec<serde_json::Value>> for Functions {
+    type Error = serde_json::Error;
+
+    fn try_from(entries: Vec<serde_json::Value>) -> Result<Self, Self::Error> {
+        entries
+            .into_iter()
+            .filter(|entry| entry.get("type").and_then(|t| t.as_str()) == Some("function"))
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()
+            .map(Functions)
+    }
+}
+
+impl Functions {
+    /// Returns the functions whose selectors the given program never pushes,
+    /// which means its dispatcher cannot call them. Dispatchers compare the
+    /// selector against PUSH4 constants, or shorter pushes if it has leading zeros.
+    pub fn undispatched(&self, program: &Program) -> Vec<&Function> {
+        let constants: HashSet<u32> = program
+            .0
+            .iter()
+            .filter_map(|op| match op {
+                Opcode::Evm(PUSH1(b)) => Some(u32::from(*b)),
+                Opcode::Evm(PUSHn(n, v, _)) if *n <= 4 => Some(v.as_u32()),
+                _ => None,
+            })
+            .collect();
+        self.0
+            .iter()
+            .filter(|func| !constants.contains(&func.selector()))
+            .collect()
+    }
+}
+
 impl IntoIterator for Functions {
     type Item = Function;
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -16,13 +55,14 @@ impl IntoIterator for Functions {
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+/// The discriminants are passed to `_evm_call_value` in the runtime library.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub enum StateMutability {
-    Nonpayable,
-    Payable,
-    Pure,
-    View,
+    Nonpayable = 0,
+    Payable = 1,
+    Pure = 2,
+    View = 3,
 }
 
 impl fmt::Display for StateMutability {
@@ -94,14 +134,12 @@ impl Function {
     }
 }
 
-#[allow(dead_code)]
 pub fn parse_str(json: &str) -> Result<Functions, serde_json::Error> {
-    serde_json::from_str::<Vec<Function>>(json).map(Functions)
+    serde_json::from_str::<Functions>(json)
 }
 
-#[allow(dead_code)]
 pub fn parse_bytes(json: &[u8]) -> Result<Functions, serde_json::Error> {
-    serde_json::from_slice::<Vec<Function>>(json).map(Functions)
+    serde_json::from_slice::<Functions>(json)
 }
 
 #[cfg(test)]
@@ -178,4 +216,15 @@ mod tests {
         let func = funcs.first().unwrap();
         assert_eq!(func.selector(), 0xcdcd77c0);
     }
+
+    #[test]
+    fn test_undispatched() {
+        let abi = parse_str(MULTIPLY).unwrap();
+        let selector = abi.0[0].selector_bytes();
+        let push4 = PUSHn(4, u32::from_be_bytes(selector).into(), selector.to_vec());
+        assert!(abi.undispatched(&Program(vec![push4.into()])).is_empty());
+        let undispatched = abi.undispatched(&Program(vec![PUSH1(0x80).into()]));
+        assert_eq!(undispatched.len(), 1);
+        assert_eq!(undispatched[0].name, "multiply");
+    }
 }
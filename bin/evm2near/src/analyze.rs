@@ -1,13 +1,13 @@
 // This is free and unencumbered software released into the public domain.
 
-use evm_rs::{Opcode, Program};
+use crate::opcode::{Opcode, Program};
 use relooper::graph::{
     cfg::{Cfg, CfgEdge},
     GraphMut,
 };
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
     ops::Range,
 };
@@ -74,6 +74,9 @@ impl<T: Display> Display for CfgNode<T> {
 pub struct BasicCfg {
     pub cfg: Cfg<CfgNode<Offs>>,
     pub code_ranges: HashMap<Offs, Range<Idx>>,
+    /// The offsets of the static jumps to offsets which are not JUMPDESTs,
+    /// which halt with an error when taken, as they do in the EVM
+    pub invalid_jumps: HashSet<Offs>,
 }
 
 pub fn basic_cfg(program: &Program) -> BasicCfg {
@@ -85,6 +88,8 @@ pub fn basic_cfg(program: &Program) -> BasicCfg {
     let mut cfg = Cfg::new(CfgNode::Orig(Offs(0)));
     let mut code_ranges: HashMap<Offs, Range<Idx>> = Default::default();
     let mut jumpdests: Vec<Offs> = Default::default();
+    // The block, the offset of the jump and its target, and where a JUMPI falls through to
+    let mut static_jumps: Vec<(Offs, Offs, Offs, Option<CfgNode<Offs>>)> = Default::default();
 
     let mut curr_offs = Offs(0);
     let mut block_start: Option<BlockStart> = None;
@@ -95,34 +100,45 @@ pub fn basic_cfg(program: &Program) -> BasicCfg {
         next_idx = Idx(curr_idx.0 + 1);
         let next_offs = Offs(curr_offs.0 + op.size());
 
-        use Opcode::*;
+        use evm_rs::Opcode::*;
         block_start = match op {
-            JUMP | JUMPI => {
+            Opcode::Evm(JUMP | JUMPI) => {
+                // A jump right after a halt or another jump is dead code, and so
+                // is left in a block of its own, to be stripped as unreachable
                 let BlockStart {
                     start_offs,
                     start_idx,
-                } = block_start.expect("block should be present at any jump opcode");
+                } = block_start.unwrap_or(BlockStart {
+                    start_offs: curr_offs,
+                    start_idx: curr_idx,
+                });
 
                 let label = match prev_op {
-                    Some(PUSH1(addr)) => Some(Offs(usize::from(*addr))),
-                    Some(PUSHn(_, addr, _)) => Some(Offs(addr.as_usize())),
-                    Some(_) => None,
-                    None => unreachable!(),
+                    Some(Opcode::Evm(PUSH1(addr))) => Some(Offs(usize::from(*addr))),
+                    Some(Opcode::Evm(PUSHn(_, addr, _))) => Some(Offs(addr.as_usize())),
+                    _ => None,
                 };
-
-                let jump_to = label.map(CfgNode::Orig).unwrap_or(CfgNode::Dynamic);
-                let edge = if op == &JUMP {
-                    CfgEdge::Uncond(jump_to)
-                } else {
-                    CfgEdge::Cond(jump_to, CfgNode::Orig(next_offs))
+                let fallthrough = match op {
+                    Opcode::Evm(JUMPI) => Some(CfgNode::Orig(next_offs)),
+                    _ => None,
                 };
-                cfg.add_edge(CfgNode::Orig(start_offs), edge);
+                match label {
+                    // Static jumps are only known to be valid once all JUMPDESTs are
+                    Some(target) => static_jumps.push((start_offs, curr_offs, target, fallthrough)),
+                    None => {
+                        let edge = match fallthrough {
+                            None => CfgEdge::Uncond(CfgNode::Dynamic),
+                            Some(next) => CfgEdge::Cond(CfgNode::Dynamic, next),
+                        };
+                        cfg.add_edge(CfgNode::Orig(start_offs), edge);
+                    }
+                }
 
                 code_ranges.insert(start_offs, start_idx..next_idx);
 
                 None
             }
-            JUMPDEST => {
+            Opcode::Evm(JUMPDEST) => {
                 jumpdests.push(curr_offs);
                 if let Some(BlockStart {
                     start_offs,
@@ -169,6 +185,27 @@ pub fn basic_cfg(program: &Program) -> BasicCfg {
     {
         code_ranges.insert(start_offs, start_idx..next_idx);
     }
+    // Running past the end of the code stops execution, like a STOP
+    let code_end = Idx(program.0.len());
+    code_ranges.entry(curr_offs).or_insert(code_end..code_end);
+
+    let jumpdest_set: HashSet<Offs> = jumpdests.iter().copied().collect();
+    let mut invalid_jumps = HashSet::new();
+    for (block, jump, target, fallthrough) in static_jumps {
+        let edge = match (jumpdest_set.contains(&target), fallthrough) {
+            (true, None) => CfgEdge::Uncond(CfgNode::Orig(target)),
+            (true, Some(next)) => CfgEdge::Cond(CfgNode::Orig(target), next),
+            // Taking an invalid jump halts, so only a JUMPI may go on
+            (false, fallthrough) => {
+                invalid_jumps.insert(jump);
+                match fallthrough {
+                    None => CfgEdge::Terminal,
+                    Some(next) => CfgEdge::Uncond(next),
+                }
+            }
+        };
+        cfg.add_edge(CfgNode::Orig(block), edge);
+    }
 
     let jump_table: Vec<_> = jumpdests
         .into_iter()
@@ -176,5 +213,45 @@ pub fn basic_cfg(program: &Program) -> BasicCfg {
         .collect();
     cfg.add_edge(CfgNode::Dynamic, CfgEdge::Switch(jump_table));
 
-    BasicCfg { cfg, code_ranges }
+    BasicCfg {
+        cfg,
+        code_ranges,
+        invalid_jumps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evm_rs::Opcode::*;
+    use relooper::graph::Graph;
+
+    fn cfg(program: Vec<evm_rs::Opcode>) -> BasicCfg {
+        let program = program.into_iter().map(Opcode::from).collect();
+        let mut basic_cfg = basic_cfg(&Program(program));
+        basic_cfg.cfg.strip_unreachable();
+        basic_cfg
+    }
+
+    #[test]
+    fn test_invalid_jumps() {
+        let basic_cfg = cfg(vec![PUSH1(4), JUMP, STOP, JUMPDEST, STOP]);
+        assert!(basic_cfg.invalid_jumps.is_empty());
+        assert_eq!(basic_cfg.cfg.nodes().len(), 2);
+
+        // As compiled by old versions of solc for `throw`
+        let basic_cfg = cfg(vec![PUSH1(3), JUMP, STOP, JUMPDEST, STOP]);
+        assert_eq!(basic_cfg.invalid_jumps, HashSet::from([Offs(2)]));
+        assert_eq!(
+            basic_cfg.cfg.edge(&CfgNode::Orig(Offs(0))),
+            &CfgEdge::Terminal
+        );
+
+        let basic_cfg = cfg(vec![PUSH1(1), PUSH1(9), JUMPI, STOP]);
+        assert_eq!(basic_cfg.invalid_jumps, HashSet::from([Offs(4)]));
+        assert_eq!(
+            basic_cfg.cfg.edge(&CfgNode::Orig(Offs(0))),
+            &CfgEdge::Uncond(CfgNode::Orig(Offs(5)))
+        );
+    }
 }
@@ -0,0 +1,318 @@
+// This is free and unencumbered software released into the public domain.
+
+use evm_rs::DecodeError;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    abi::Functions, decode::decode_bytecode, error::FrontendError, format::InputFormat,
+    metadata::Metadata, opcode::Program,
+};
+
+/// A contract compiled by a build tool, with its runtime bytecode and ABI.
+pub struct Artifact {
+    pub program: Program,
+    pub metadata: Option<Metadata>,
+    pub abi: Functions,
+}
+
+/// A Foundry artifact, as found in `out/<File>.sol/<Contract>.json`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all(deserialize = "camelCase"))]
+struct FoundryArtifact {
+    abi: Functions,
+    deployed_bytecode: BytecodeObject,
+}
+
+/// Bytecode as output by `solc`, which Foundry artifacts keep as is.
+#[derive(Deserialize, Debug, Default)]
+struct BytecodeObject {
+    object: String,
+}
+
+/// A Hardhat artifact, as found in `artifacts/<File>.sol/<Contract>.json`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all(deserialize = "camelCase"))]
+struct HardhatArtifact {
+    abi: Functions,
+    deployed_bytecode: String,
+}
+
+/// The output of `solc --standard-json`, with the contracts of each source file.
+/// See: https://docs.soliditylang.org/en/develop/using-the-compiler.html#output-description
+#[derive(Deserialize, Debug)]
+struct SolcOutput {
+    contracts: BTreeMap<String, BTreeMap<String, SolcContract>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SolcContract {
+    #[serde(default)]
+    abi: Functions,
+    #[serde(default)]
+    evm: SolcEvm,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all(deserialize = "camelCase"))]
+struct SolcEvm {
+    #[serde(default)]
+    deployed_bytecode: BytecodeObject,
+}
+
+#[derive(Debug)]
+pub enum ArtifactError {
+    Json(serde_json::Error),
+    /// The contract is abstract or an interface
+    Empty,
+    /// The contract calls libraries that must be linked first
+    Unlinked,
+    Decode(DecodeError),
+    /// The contract to compile could not be chosen, as with source code
+    Contract(FrontendError),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ArtifactError::*;
+        match self {
+            Json(err) => write!(f, "invalid artifact: {}", err),
+            Empty => write!(f, "no deployed bytecode (abstract contract or interface)"),
+            Unlinked => write!(f, "unlinked library references in the deployed bytecode"),
+            Decode(err) => write!(f, "invalid deployed bytecode: {}", err),
+            Contract(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Tells Foundry and Hardhat artifacts apart, by the shape of their deployed bytecode,
+/// and `solc` output, which has the contracts of each source file.
+pub fn detect(input: &str) -> Option<InputFormat> {
+    let json: serde_json::Value = serde_json::from_str(input).ok()?;
+    if let Some(serde_json::Value::Object(_)) = json.get("contracts") {
+        return Some(InputFormat::SolcJson);
+    }
+    json.get("abi")?;
+    match json.get("deployedBytecode")? {
+        serde_json::Value::String(_) => Some(InputFormat::Hardhat),
+        serde_json::Value::Object(bytecode) if bytecode.contains_key("object") => {
+            Some(InputFormat::Foundry)
+        }
+        _ => None,
+    }
+}
+
+/// Whether a Foundry or Hardhat artifact has deployed bytecode, which the
+/// artifacts of interfaces and abstract contracts do not.
+pub fn is_deployable(input: &str) -> bool {
+    let json: serde_json::Value = match serde_json::from_str(input) {
+        Ok(json) => json,
+        Err(_) => return false,
+    };
+    let bytecode = match json.get("deployedBytecode") {
+        Some(serde_json::Value::Object(bytecode)) => bytecode.get("object"),
+        bytecode => bytecode,
+    };
+    match bytecode.and_then(serde_json::Value::as_str) {
+        Some(bytecode) => !bytecode.trim_start_matches("0x").is_empty(),
+        None => false,
+    }
+}
+
+pub fn parse_foundry(input: &str) -> Result<Artifact, ArtifactError> {
+    let artifact: FoundryArtifact = serde_json::from_str(input).map_err(ArtifactError::Json)?;
+    let (program, metadata) = decode_deployed_bytecode(&artifact.deployed_bytecode.object)?;
+    Ok(Artifact {
+        program,
+        metadata,
+        abi: artifact.abi,
+    })
+}
+
+pub fn parse_hardhat(input: &str) -> Result<Artifact, ArtifactError> {
+    let artifact: HardhatArtifact = serde_json::from_str(input).map_err(ArtifactError::Json)?;
+    let (program, metadata) = decode_deployed_bytecode(&artifact.deployed_bytecode)?;
+    Ok(Artifact {
+        program,
+        metadata,
+        abi: artifact.abi,
+    })
+}
+
+/// Reads the contract of the given name, which may be qualified with its source
+/// file as in `src/Token.sol:Token`, or else the only contract that can be deployed.
+pub fn parse_solc_json(
+    input: &str,
+    contract_name: Option<&str>,
+) -> Result<Artifact, ArtifactError> {
+    let output: SolcOutput = serde_json::from_str(input).map_err(ArtifactError::Json)?;
+    // Interfaces and abstract contracts have no deployed bytecode
+    let contracts: Vec<(String, SolcContract)> = output
+        .contracts
+        .into_iter()
+        .flat_map(|(source_name, contracts)| {
+            contracts
+                .into_iter()
+                .map(move |(name, contract)| (format!("{}:{}", source_name, name), contract))
+        })
+        .filter(|(_, contract)| !contract.evm.deployed_bytecode.object.is_empty())
+        .collect();
+    let names: Vec<String> = contracts.iter().map(|(name, _)| name.clone()).collect();
+    let mut found: Vec<(String, SolcContract)> = contracts
+        .into_iter()
+        .filter(|(qualified_name, _)| match contract_name {
+            Some(name) if name.contains(':') => qualified_name == name,
+            Some(name) => qualified_name.ends_with(&format!(":{}", name)),
+            None => true,
+        })
+        .collect();
+    let contract = match (found.len(), contract_name) {
+        (1, _) => found.remove(0).1,
+        (0, Some(name)) => {
+            let err = FrontendError::UnknownContract(name.to_string(), names);
+            return Err(ArtifactError::Contract(err));
+        }
+        _ => {
+            let names = found.into_iter().map(|(name, _)| name).collect();
+            return Err(ArtifactError::Contract(FrontendError::AmbiguousContract(
+                names,
+            )));
+        }
+    };
+    let (program, metadata) = decode_deployed_bytecode(&contract.evm.deployed_bytecode.object)?;
+    Ok(Artifact {
+        program,
+        metadata,
+        abi: contract.abi,
+    })
+}
+
+fn decode_deployed_bytecode(bytecode: &str) -> Result<(Program, Option<Metadata>), ArtifactError> {
+    let hex = bytecode.trim_start_matches("0x");
+    if hex.is_empty() {
+        return Err(ArtifactError::Empty);
+    }
+    // Library addresses are left as `__$<hash>$__` placeholders until linked
+    if hex.contains("__") {
+        return Err(ArtifactError::Unlinked);
+    }
+    decode_bytecode(hex).map_err(ArtifactError::Decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FOUNDRY: &str = r#"{
+        "abi": [
+            {"type":"constructor","inputs":[],"stateMutability":"nonpayable"},
+            {"type":"function","name":"count","inputs":[],"outputs":[{"name":"","type":"uint256","internalType":"uint256"}],"stateMutability":"view"},
+            {"type":"event","name":"Incremented","inputs":[],"anonymous":false}
+        ],
+        "bytecode": {"object":"0x6080604052","sourceMap":"","linkReferences":{}},
+        "deployedBytecode": {"object":"0x6080604052","sourceMap":"","linkReferences":{}},
+        "methodIdentifiers": {"count()":"06661abd"}
+    }"#;
+
+    static HARDHAT: &str = r#"{
+        "_format": "hh-sol-artifact-1",
+        "contractName": "Counter",
+        "sourceName": "contracts/Counter.sol",
+        "abi": [
+            {"type":"function","name":"count","inputs":[],"outputs":[{"name":"","type":"uint256","internalType":"uint256"}],"stateMutability":"view"}
+        ],
+        "bytecode": "0x6080604052",
+        "deployedBytecode": "0x6080604052",
+        "linkReferences": {},
+        "deployedLinkReferences": {}
+    }"#;
+
+    static SOLC_JSON: &str = r#"{
+        "contracts": {
+            "src/Counter.sol": {
+                "Counter": {
+                    "abi": [
+                        {"type":"function","name":"count","inputs":[],"outputs":[{"name":"","type":"uint256","internalType":"uint256"}],"stateMutability":"view"}
+                    ],
+                    "evm": {"deployedBytecode": {"object": "6080604052"}}
+                },
+                "ICounter": {
+                    "abi": [],
+                    "evm": {"deployedBytecode": {"object": ""}}
+                }
+            },
+            "src/Token.sol": {
+                "Token": {
+                    "abi": [],
+                    "evm": {"deployedBytecode": {"object": "6080"}}
+                }
+            }
+        },
+        "sources": {"src/Counter.sol": {"id": 0}, "src/Token.sol": {"id": 1}}
+    }"#;
+
+    #[test]
+    fn test_detect() {
+        assert!(matches!(detect(FOUNDRY), Some(InputFormat::Foundry)));
+        assert!(matches!(detect(HARDHAT), Some(InputFormat::Hardhat)));
+        assert!(is_deployable(FOUNDRY));
+        assert!(is_deployable(HARDHAT));
+        assert!(!is_deployable(
+            r#"{"abi": [], "deployedBytecode": {"object": "0x"}}"#
+        ));
+        assert!(!is_deployable(r#"{"abi": [], "deployedBytecode": "0x"}"#));
+        assert!(matches!(detect(SOLC_JSON), Some(InputFormat::SolcJson)));
+        assert!(detect(r#"{"abi": []}"#).is_none());
+        assert!(detect("6080604052").is_none());
+    }
+
+    #[test]
+    fn test_parse() {
+        let artifact = parse_foundry(FOUNDRY).unwrap();
+        let names: Vec<String> = artifact.abi.into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["count"]);
+
+        let artifact = parse_hardhat(HARDHAT).unwrap();
+        assert_eq!(artifact.abi.into_iter().count(), 1);
+        assert!(matches!(
+            parse_hardhat(FOUNDRY).err(),
+            Some(ArtifactError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_solc_json() {
+        let artifact = parse_solc_json(SOLC_JSON, Some("Counter")).unwrap();
+        let names: Vec<String> = artifact.abi.into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["count"]);
+        let artifact = parse_solc_json(SOLC_JSON, Some("src/Token.sol:Token")).unwrap();
+        assert_eq!(artifact.abi.into_iter().count(), 0);
+
+        assert_eq!(
+            parse_solc_json(SOLC_JSON, None).err().unwrap().to_string(),
+            "several contracts in the input, choose one with `--contract': \
+             src/Counter.sol:Counter, src/Token.sol:Token"
+        );
+        // Interfaces cannot be deployed
+        assert!(matches!(
+            parse_solc_json(SOLC_JSON, Some("ICounter")),
+            Err(ArtifactError::Contract(FrontendError::UnknownContract(..)))
+        ));
+        assert!(matches!(
+            parse_solc_json(SOLC_JSON, Some("src/Token.sol:Counter")),
+            Err(ArtifactError::Contract(FrontendError::UnknownContract(..)))
+        ));
+    }
+
+    #[test]
+    fn test_decode_deployed_bytecode() {
+        assert!(matches!(
+            decode_deployed_bytecode("0x"),
+            Err(ArtifactError::Empty)
+        ));
+        assert!(matches!(
+            decode_deployed_bytecode("0x73__$1b2c3d$__6080"),
+            Err(ArtifactError::Unlinked)
+        ));
+    }
+}
@@ -5,22 +5,24 @@ use std::{
     collections::{HashMap, HashSet},
     convert::TryInto,
     fmt::Display,
-    fs::File,
-    io::Write,
+    fs::{self, File},
+    io,
     path::PathBuf,
 };
 
-use evm_rs::{parse_opcode, Opcode, Program};
 use relooper::graph::{enrichments::EnrichedCfg, relooper::ReBlock};
 use relooper::graph::{reduction::SLabel, relooper::ReSeq};
 use wasm_encoder::{BlockType, ExportKind, Function, Instruction, Module, ValType};
 
 use crate::{
-    abi::Functions,
+    abi::{Functions, StateMutability},
     analyze::{basic_cfg, BasicCfg, CfgNode, Idx, Offs},
     config::CompilerConfig,
     encode::encode_push,
-    wasm_translate::{translator::DataMode, Export, ModuleBuilder, Signature},
+    error::{Diagnostic, Error},
+    metadata::Metadata,
+    opcode::{parse_opcode, Opcode, Program},
+    wasm_translate::{translator::DataMode, Export, Glob, ModuleBuilder, Signature},
 };
 
 const TABLE_OFFSET: i32 = 0x1000;
@@ -63,16 +65,28 @@ fn evm_idx_to_offs(program: &Program) -> HashMap<Idx, Offs> {
 
 pub fn compile<'a>(
     input_program: &'a Program,
+    input_metadata: Option<&Metadata>,
     input_abi: Option<Functions>,
     runtime_library: ModuleBuilder<'a>,
     config: CompilerConfig,
-) -> Module {
-    let mut compiler = Compiler::new(runtime_library, config);
+) -> Result<Module, Error> {
+    config.check()?;
+    let mut compiler = Compiler::new(runtime_library, config)?;
+    compiler.create_debug_dir()?;
+    if let Some(metadata) = input_metadata {
+        compiler.debug("metadata.txt", || format!("{}\n", metadata))?;
+    }
     compiler.emit_wasm_start();
     compiler.emit_evm_start();
-    flame::span_of("compiling cfg", || compiler.compile_cfg(input_program));
+    flame::span_of("compiling cfg", || compiler.compile_cfg(input_program))?;
     compiler.emit_abi_execute();
-    let abi_data = compiler.emit_abi_methods(input_abi).unwrap();
+    compiler.emit_abi_address_of();
+    compiler.emit_abi_migrate();
+    let mut abi_data = compiler.emit_abi_methods(input_abi)?;
+    compiler.emit_abi_upgrade(&mut abi_data)?;
+    if abi_data.len() > compiler.abi_buffer_len {
+        return Err(Error::AbiOverflow(abi_data.len()));
+    }
 
     let abi_buffer_ptr: usize = compiler.abi_buffer_off.try_into().unwrap();
     for data in compiler.builder.data.iter_mut() {
@@ -88,17 +102,17 @@ pub fn compile<'a>(
         if abi_buffer_ptr >= min_ptr && abi_buffer_ptr < max_ptr {
             let min_off = abi_buffer_ptr - min_ptr;
             let max_off = min_off + abi_data.len();
-            assert!(min_ptr + max_off <= max_ptr);
+            if min_ptr + max_off > max_ptr {
+                return Err(Error::AbiOverflow(abi_data.len()));
+            }
             data.data[min_off..max_off].copy_from_slice(&abi_data);
             break; // found it
         }
     }
 
-    compiler.debug_write("flamegraph.html", |w| {
-        flame::dump_html(w).expect("flamegraph dump error")
-    });
+    compiler.debug_write("flamegraph.html", |w| flame::dump_html(w))?;
 
-    compiler.builder.build()
+    Ok(compiler.builder.build())
 }
 
 type DataOffset = i32;
@@ -109,55 +123,88 @@ struct Compiler<'a> {
     abi_buffer_off: DataOffset,
     abi_buffer_len: usize,
     op_table: HashMap<Opcode, FunctionIndex>,
-    evm_start_function: FunctionIndex,     // _evm_start
-    evm_init_function: FunctionIndex,      // _evm_init
-    evm_call_function: FunctionIndex,      // _evm_call
-    evm_exec_function: FunctionIndex,      // _evm_exec
-    evm_post_exec_function: FunctionIndex, // _evm_post_exec
-    evm_pop_function: FunctionIndex,       // _evm_pop_u32
-    evm_burn_gas: FunctionIndex,           // _evm_burn_gas
-    evm_pc_function: FunctionIndex,        // _evm_set_pc
+    evm_start_function: FunctionIndex,      // _evm_start
+    evm_init_function: FunctionIndex,       // _evm_init
+    evm_call_function: FunctionIndex,       // _evm_call
+    evm_call_value_function: FunctionIndex, // _evm_call_value
+    evm_address_of_function: FunctionIndex, // _evm_address_of
+    evm_migrate_function: FunctionIndex,    // _evm_migrate
+    evm_upgrade_function: FunctionIndex,    // _evm_upgrade
+    evm_exec_function: FunctionIndex,       // _evm_exec
+    evm_post_exec_function: FunctionIndex,  // _evm_post_exec
+    evm_pop_function: FunctionIndex,        // _evm_pop_u32
+    evm_burn_gas: FunctionIndex,            // _evm_burn_gas
+    evm_pc_function: FunctionIndex,         // _evm_set_pc
     builder: ModuleBuilder<'a>,
 }
 
 impl<'a> Compiler<'a> {
     /// Instantiates a new compiler state.
-    fn new(runtime_library: ModuleBuilder, config: CompilerConfig) -> Compiler {
-        Compiler {
+    fn new(runtime_library: ModuleBuilder, config: CompilerConfig) -> Result<Compiler, Error> {
+        let runtime_function = |name| find_runtime_function(&runtime_library, name);
+        Ok(Compiler {
             config,
-            abi_buffer_off: find_abi_buffer(&runtime_library).unwrap(),
+            abi_buffer_off: find_abi_buffer(&runtime_library)?,
             abi_buffer_len: 0xFFFF, // TODO: ensure this matches _abi_buffer.len() in evmlib
-            op_table: make_op_table(&runtime_library),
+            op_table: make_op_table(&runtime_library)?,
             evm_start_function: 0, // filled in during emit_start()
-            evm_init_function: find_runtime_function(&runtime_library, "_evm_init").unwrap(),
-            evm_call_function: find_runtime_function(&runtime_library, "_evm_call").unwrap(),
-            evm_post_exec_function: find_runtime_function(&runtime_library, "_evm_post_exec")
-                .unwrap(),
+            evm_init_function: runtime_function("_evm_init")?,
+            evm_call_function: runtime_function("_evm_call")?,
+            evm_call_value_function: runtime_function("_evm_call_value")?,
+            evm_address_of_function: runtime_function("_evm_address_of")?,
+            evm_migrate_function: runtime_function("_evm_migrate")?,
+            evm_upgrade_function: runtime_function("_evm_upgrade")?,
+            evm_post_exec_function: runtime_function("_evm_post_exec")?,
             evm_exec_function: 0, // filled in during compile_cfg()
-            evm_pop_function: find_runtime_function(&runtime_library, "_evm_pop_u32").unwrap(),
-            evm_burn_gas: find_runtime_function(&runtime_library, "_evm_burn_gas").unwrap(),
-            evm_pc_function: find_runtime_function(&runtime_library, "_evm_set_pc").unwrap(),
+            evm_pop_function: runtime_function("_evm_pop_u32")?,
+            evm_burn_gas: runtime_function("_evm_burn_gas")?,
+            evm_pc_function: runtime_function("_evm_set_pc")?,
             builder: runtime_library,
+        })
+    }
+
+    /// Empties the debug directory, if any, so that it only contains fresh files.
+    fn create_debug_dir(&self) -> Result<(), Error> {
+        if let Some(debug_dir) = &self.config.debug_path {
+            let result = match fs::read_dir(debug_dir) {
+                Ok(_) => fs::remove_dir_all(debug_dir).and_then(|_| fs::create_dir_all(debug_dir)),
+                Err(_) => fs::create_dir_all(debug_dir),
+            };
+            result.map_err(|err| Error::Debug(debug_dir.clone(), err))?;
         }
+        Ok(())
     }
 
-    fn debug<TPath: Into<PathBuf>, CF: Fn() -> String>(&self, path: TPath, contents: CF) {
+    fn debug<TPath: Into<PathBuf>, CF: Fn() -> String>(
+        &self,
+        path: TPath,
+        contents: CF,
+    ) -> Result<(), Error> {
         if let Some(base_path) = &self.config.debug_path {
             let mut full_path = base_path.clone();
             full_path.push(path.into());
 
-            std::fs::write(full_path, contents()).expect("fs error while writing debug file");
+            if let Err(err) = fs::write(&full_path, contents()) {
+                return Err(Error::Debug(full_path, err));
+            }
         }
+        Ok(())
     }
 
-    fn debug_write<TPath: Into<PathBuf>, CF: Fn(&File)>(&self, path: TPath, writer: CF) {
+    fn debug_write<TPath: Into<PathBuf>, CF: Fn(&File) -> io::Result<()>>(
+        &self,
+        path: TPath,
+        writer: CF,
+    ) -> Result<(), Error> {
         if let Some(base_path) = &self.config.debug_path {
             let mut full_path = base_path.clone();
             full_path.push(path.into());
 
-            let w = std::fs::File::create(full_path).expect("fs error while writing debug file");
-            writer(&w);
+            if let Err(err) = File::create(&full_path).and_then(|w| writer(&w)) {
+                return Err(Error::Debug(full_path, err));
+            }
         }
+        Ok(())
     }
 
     /// Emit an empty `_start` function to make all WebAssembly runtimes happy.
@@ -174,8 +221,8 @@ impl<'a> Compiler<'a> {
             Some("_evm_start".to_string()),
             vec![
                 Instruction::I32Const(TABLE_OFFSET),
-                Instruction::I64Const(self.config.chain_id.try_into().unwrap()), // --chain-id
-                Instruction::I64Const(0),                                        // TODO: --balance
+                Instruction::I64Const(self.config.chain_id as i64), // --chain-id
+                Instruction::I32Const(self.config.value_scale.into()), // --value-scale
                 Instruction::Call(self.evm_init_function),
             ],
         );
@@ -189,6 +236,10 @@ impl<'a> Compiler<'a> {
             Some("execute".to_string()),
             vec![
                 Instruction::Call(self.evm_start_function),
+                // The EVM dispatcher rejects unexpected values itself:
+                Instruction::I32Const(StateMutability::Payable as i32),
+                Instruction::Call(self.evm_call_value_function),
+                Instruction::Drop,
                 Instruction::Call(self.evm_exec_function),
                 Instruction::I32Const(0),
                 Instruction::I32Const(0), // output_types_len == 0 means no JSON encoding
@@ -197,15 +248,70 @@ impl<'a> Compiler<'a> {
         );
     }
 
+    /// Synthesizes a public `evm_address_of` view method, which tells users
+    /// the EVM address that a given NEAR account appears as to the contract.
+    fn emit_abi_address_of(&mut self) {
+        assert_ne!(self.evm_start_function, 0);
+
+        _ = self.emit_function(
+            Some("evm_address_of".to_string()),
+            vec![
+                Instruction::Call(self.evm_start_function),
+                Instruction::Call(self.evm_address_of_function),
+            ],
+        );
+    }
+
+    /// Synthesizes a public `evm_migrate` method, which moves the given storage
+    /// slots to the latest storage layout of the runtime. Like the other
+    /// synthesized methods, its name keeps clear of ABI functions like `migrate`.
+    fn emit_abi_migrate(&mut self) {
+        assert_ne!(self.evm_start_function, 0);
+
+        _ = self.emit_function(
+            Some("evm_migrate".to_string()),
+            vec![
+                Instruction::Call(self.evm_start_function),
+                Instruction::Call(self.evm_migrate_function),
+            ],
+        );
+    }
+
+    /// Synthesizes a public `evm_upgrade` method if the contract has an owner,
+    /// which deploys the WebAssembly code given as input in place of the contract,
+    /// keeping its storage. The owner's account ID is appended to the ABI data.
+    fn emit_abi_upgrade(&mut self, data: &mut Vec<u8>) -> Result<(), Error> {
+        assert_ne!(self.evm_start_function, 0);
+
+        let owner = match &self.config.upgrade_owner {
+            Some(owner) => owner,
+            None => return Ok(()),
+        };
+        self.check_method_name("evm_upgrade")?;
+        let owner_off = data.len();
+        data.extend_from_slice(owner.as_bytes());
+        let owner_len = data.len() - owner_off;
+        data.push(0); // NUL
+
+        _ = self.emit_function(
+            Some("evm_upgrade".to_string()),
+            vec![
+                Instruction::Call(self.evm_start_function),
+                Instruction::I32Const(owner_off.try_into().unwrap()), // owner_off
+                Instruction::I32Const(owner_len.try_into().unwrap()), // owner_len
+                Instruction::Call(self.evm_upgrade_function),
+            ],
+        );
+        Ok(())
+    }
+
     /// Synthesizes public wrapper methods for each function in the Solidity
     /// contract's ABI, enabling users to directly call a contract method
     /// without going through the low-level `execute` EVM dispatcher.
-    pub fn emit_abi_methods(
-        &mut self,
-        input_abi: Option<Functions>,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    pub fn emit_abi_methods(&mut self, input_abi: Option<Functions>) -> Result<Vec<u8>, Error> {
         assert_ne!(self.evm_start_function, 0);
         assert_ne!(self.evm_call_function, 0);
+        assert_ne!(self.evm_call_value_function, 0);
         assert_ne!(self.evm_exec_function, 0); // filled in during compile_cfg()
 
         let mut data = Vec::with_capacity(self.abi_buffer_len);
@@ -213,9 +319,9 @@ impl<'a> Compiler<'a> {
             let names_off = data.len();
             for (i, input) in func.inputs.iter().enumerate() {
                 if i > 0 {
-                    write!(data, ",")?;
+                    data.push(b',');
                 }
-                write!(data, "{}", input.name)?;
+                data.extend_from_slice(input.name.as_bytes());
             }
             let names_len = data.len() - names_off;
             data.push(0); // NUL
@@ -223,12 +329,12 @@ impl<'a> Compiler<'a> {
             let types_off = data.len();
             for (i, input) in func.inputs.iter().enumerate() {
                 if i > 0 {
-                    write!(data, ",")?;
+                    data.push(b',');
                 }
                 if abi_types::parse_param_type(&input.r#type).is_err() {
-                    panic!("Unknown ABI type: {}", input.r#type);
+                    return Err(Error::AbiType(input.r#type.clone(), func.to_string()));
                 }
-                write!(data, "{}", input.r#type)?;
+                data.extend_from_slice(input.r#type.as_bytes());
             }
             let types_len = data.len() - types_off;
             data.push(0); // NUL
@@ -236,20 +342,25 @@ impl<'a> Compiler<'a> {
             let output_types_off = data.len();
             for (i, output) in func.outputs.iter().enumerate() {
                 if i > 0 {
-                    write!(data, ",")?;
+                    data.push(b',');
                 }
                 if abi_types::parse_param_type(&output.r#type).is_err() {
-                    panic!("Unknown ABI type: {}", output.r#type);
+                    return Err(Error::AbiType(output.r#type.clone(), func.to_string()));
                 }
-                write!(data, "{}", output.r#type)?;
+                data.extend_from_slice(output.r#type.as_bytes());
             }
             let output_types_len = data.len() - output_types_off;
             data.push(0); // NUL
 
+            self.check_method_name(&func.name)?;
             _ = self.emit_function(
                 Some(func.name.clone()),
                 vec![
                     Instruction::Call(self.evm_start_function),
+                    // Reject attached deposits early for non-payable methods:
+                    Instruction::I32Const(func.state_mutability as i32),
+                    Instruction::Call(self.evm_call_value_function),
+                    Instruction::If(BlockType::Empty),
                     Instruction::I32Const(func.selector() as i32),
                     Instruction::I32Const(names_off.try_into().unwrap()), // params_names_ptr
                     Instruction::I32Const(names_len.try_into().unwrap()), // params_names_len
@@ -257,6 +368,7 @@ impl<'a> Compiler<'a> {
                     Instruction::I32Const(types_len.try_into().unwrap()), // params_types_len
                     Instruction::Call(self.evm_call_function),
                     Instruction::Call(self.evm_exec_function),
+                    Instruction::End,
                     Instruction::I32Const(output_types_off.try_into().unwrap()), // output_types_off
                     Instruction::I32Const(output_types_len.try_into().unwrap()), // output_types_len
                     Instruction::Call(self.evm_post_exec_function),
@@ -271,27 +383,28 @@ impl<'a> Compiler<'a> {
         &self,
         program: &'a Program,
         cfg_part: &ReSeq<SLabel<CfgNode<EvmBlock>>>,
+        invalid_jumps: &HashSet<Offs>,
         res: &mut Vec<Instruction<'a>>,
         wasm_idx2evm_idx: &mut HashMap<Idx, Idx>,
-    ) {
+    ) -> Result<(), Diagnostic> {
         for block in cfg_part.0.iter() {
             match block {
                 ReBlock::Block(inner_seq) => {
                     res.push(Instruction::Block(BlockType::Empty));
-                    self.unfold_cfg(program, inner_seq, res, wasm_idx2evm_idx);
+                    self.unfold_cfg(program, inner_seq, invalid_jumps, res, wasm_idx2evm_idx)?;
                     res.push(Instruction::End);
                 }
                 ReBlock::Loop(inner_seq) => {
                     res.push(Instruction::Loop(BlockType::Empty));
-                    self.unfold_cfg(program, inner_seq, res, wasm_idx2evm_idx);
+                    self.unfold_cfg(program, inner_seq, invalid_jumps, res, wasm_idx2evm_idx)?;
                     res.push(Instruction::End);
                 }
                 ReBlock::If(true_branch, false_branch) => {
                     res.push(Instruction::Call(self.evm_pop_function));
                     res.push(Instruction::If(BlockType::Empty));
-                    self.unfold_cfg(program, true_branch, res, wasm_idx2evm_idx);
+                    self.unfold_cfg(program, true_branch, invalid_jumps, res, wasm_idx2evm_idx)?;
                     res.push(Instruction::Else);
-                    self.unfold_cfg(program, false_branch, res, wasm_idx2evm_idx);
+                    self.unfold_cfg(program, false_branch, invalid_jumps, res, wasm_idx2evm_idx)?;
                     res.push(Instruction::End);
                 }
                 ReBlock::Br(levels) => {
@@ -312,19 +425,46 @@ impl<'a> Compiler<'a> {
                                 match &block_code[curr_idx..] {
                                     [p, j, ..] if p.is_push() && j.is_jump() => {
                                         // this is static jump, already accounted during cfg analysis. we only need to burn gas there
-                                        let jump_gas = if j == &Opcode::JUMP { 8 } else { 10 };
+                                        let jump_gas = if j == &Opcode::Evm(evm_rs::Opcode::JUMP) {
+                                            8
+                                        } else {
+                                            10
+                                        };
                                         res.extend(vec![
                                             Instruction::I32Const(3),             // any push costs 3 gas
                                             Instruction::Call(self.evm_burn_gas), // burn it
                                             Instruction::I32Const(jump_gas),
                                             Instruction::Call(self.evm_burn_gas),
                                         ]);
+                                        // a jump to an offset which is not a JUMPDEST halts like INVALID
+                                        let jump_offs = orig_label.label.0 + evm_offset + p.size();
+                                        if invalid_jumps.contains(&Offs(jump_offs)) {
+                                            let invalid = self.compile_operator(
+                                                &Opcode::Evm(evm_rs::Opcode::INVALID),
+                                                jump_offs,
+                                            )?;
+                                            if j == &Opcode::Evm(evm_rs::Opcode::JUMP) {
+                                                res.extend(vec![invalid, Instruction::Return]);
+                                            } else {
+                                                res.extend(vec![
+                                                    Instruction::Call(self.evm_pop_function),
+                                                    Instruction::If(BlockType::Empty),
+                                                    invalid,
+                                                    Instruction::Return,
+                                                    Instruction::End,
+                                                ]);
+                                            }
+                                        }
                                         curr_idx += 2;
                                         evm_offset += p.size() + j.size();
                                     }
                                     [j, ..] if j.is_jump() => {
                                         // this is dynamic jump
-                                        let jump_gas = if j == &Opcode::JUMP { 8 } else { 10 };
+                                        let jump_gas = if j == &Opcode::Evm(evm_rs::Opcode::JUMP) {
+                                            8
+                                        } else {
+                                            10
+                                        };
                                         res.extend(vec![
                                             Instruction::I32Const(jump_gas),
                                             Instruction::Call(self.evm_burn_gas),
@@ -337,20 +477,20 @@ impl<'a> Compiler<'a> {
                                             Idx(res.len()),
                                             Idx(curr_idx + orig_label.code_start.0),
                                         );
+                                        let pc = orig_label.label.0 + evm_offset;
                                         if self.config.program_counter {
-                                            let pc = orig_label.label.0 + evm_offset;
                                             res.extend(vec![
                                                 Instruction::I32Const(pc.try_into().unwrap()),
                                                 Instruction::Call(self.evm_pc_function),
                                             ]);
                                         }
-                                        if op.is_push() {
-                                            let operands = encode_push(op);
+                                        if let Some(push) = op.as_push() {
+                                            let operands = encode_push(push);
                                             res.extend(operands);
                                         }
-                                        let call = self.compile_operator(op);
+                                        let call = self.compile_operator(op, pc)?;
                                         res.push(call);
-                                        if op == &Opcode::RETURN {
+                                        if op == &Opcode::Evm(evm_rs::Opcode::RETURN) {
                                             res.push(Instruction::Return);
                                         }
                                         curr_idx += 1;
@@ -366,8 +506,9 @@ impl<'a> Compiler<'a> {
                     }
                 }
                 ReBlock::TableJump(table) => {
-                    let (table_len, _) = table.last_key_value().unwrap(); // should be safe as switch of zero variants is meaningless
-                    let mut linear_table = vec![0; table_len + 1];
+                    // Without any JUMPDEST, every dynamic jump ends up as unreachable
+                    let table_len = table.last_key_value().map_or(0, |(cond, _)| cond + 1);
+                    let mut linear_table = vec![0; table_len];
                     for (&cond, &br_num) in table {
                         linear_table[cond] = br_num + 1; // increment due to additional block wrapping (for unreachable instruction)
                     }
@@ -382,15 +523,16 @@ impl<'a> Compiler<'a> {
                 }
             }
         }
+        Ok(())
     }
 
-    fn opcodes_debug(&self, program: &Program) {
+    fn opcodes_debug(&self, program: &Program) -> Result<(), Error> {
         let mut opcode_lines: Vec<String> = vec![];
         program.0.iter().fold(Offs(0), |offs, opcode| {
             opcode_lines.push(format!("0x{:02x}\t{}", offs.0, opcode));
             Offs(offs.0 + opcode.size())
         });
-        self.debug("opcodes.evm", || opcode_lines.join("\n"));
+        self.debug("opcodes.evm", || opcode_lines.join("\n"))
     }
 
     fn evm_wasm_dot_debug(
@@ -399,7 +541,7 @@ impl<'a> Compiler<'a> {
         basic_cfg: &BasicCfg,
         wasm: &[Instruction],
         wasm_idx2evm_idx: &HashMap<Idx, Idx>,
-    ) {
+    ) -> Result<(), Error> {
         let evm_idx2offs = evm_idx_to_offs(program);
 
         let mut code_ranges: Vec<_> = basic_cfg.code_ranges.iter().collect();
@@ -490,39 +632,40 @@ subgraph cluster_wasm {{ label = \"wasm\"
                 wasm_lines.join("\n"),
                 wasm2evm_lines.join("\n")
             )
-        });
+        })
     }
 
     /// Compiles the program's control-flow graph.
-    fn compile_cfg(&mut self, program: &'a Program) {
+    fn compile_cfg(&mut self, program: &'a Program) -> Result<(), Error> {
         assert_ne!(self.evm_start_function, 0); // filled in during emit_start()
         assert_eq!(self.evm_exec_function, 0); // filled in below
 
-        self.opcodes_debug(program);
+        self.opcodes_debug(program)?;
 
-        let basic_cfg = flame::span_of("building basic cfg", || basic_cfg(program));
+        let mut basic_cfg = flame::span_of("building basic cfg", || basic_cfg(program));
         self.debug("basic_cfg.dot", || {
             format!("digraph {{{}}}", basic_cfg.cfg.cfg_to_dot("basic"))
-        });
+        })?;
+        basic_cfg.cfg.strip_unreachable();
 
-        let mut evm_cfg = basic_cfg.cfg.map_label(|n| match n {
+        let evm_cfg = basic_cfg.cfg.map_label(|n| match n {
             CfgNode::Orig(l) => {
+                // Every block start has a code range, and jumps only go to block starts
                 let a = basic_cfg.code_ranges.get(l).unwrap();
                 CfgNode::Orig(EvmBlock::new(*l, a.start, a.end))
             }
             CfgNode::Dynamic => CfgNode::Dynamic,
         });
 
-        evm_cfg.strip_unreachable();
         self.debug("stripped.dot", || {
             format!("digraph {{{}}}", evm_cfg.cfg_to_dot("stripped"))
-        });
+        })?;
         let reduced = relooper::graph::reduction::reduce(&evm_cfg);
         // println!("node count: {}", reduced.nodes().len());
 
         self.debug("reduced.dot", || {
             format!("digraph {{{}}}", reduced.cfg_to_dot("reduced"))
-        });
+        })?;
         let enriched = flame::span_of("enriching cfg", || EnrichedCfg::new(reduced));
         self.debug("enriched.dot", || {
             format!(
@@ -530,31 +673,57 @@ subgraph cluster_wasm {{ label = \"wasm\"
                 enriched.cfg_to_dot("enriched"),
                 enriched.dom_to_dot()
             )
-        });
+        })?;
         let relooped_cfg = flame::span_of("relooping", || enriched.reloop());
 
         self.debug("relooped.dot", || {
             format!("digraph {{{}}}", relooped_cfg.to_dot())
-        });
+        })?;
 
         let mut wasm: Vec<Instruction> = Default::default();
         let mut wasm_idx2evm_idx = Default::default();
-        self.unfold_cfg(program, &relooped_cfg, &mut wasm, &mut wasm_idx2evm_idx);
+        self.unfold_cfg(
+            program,
+            &relooped_cfg,
+            &basic_cfg.invalid_jumps,
+            &mut wasm,
+            &mut wasm_idx2evm_idx,
+        )?;
         wasm.push(Instruction::End);
 
         if self.config.debug_path.is_some() {
-            self.evm_wasm_dot_debug(program, &basic_cfg, &wasm, &wasm_idx2evm_idx);
+            self.evm_wasm_dot_debug(program, &basic_cfg, &wasm, &wasm_idx2evm_idx)?;
         }
 
         let func_id = self.emit_function(Some("_evm_exec".to_string()), wasm);
         self.evm_exec_function = func_id;
+        Ok(())
     }
 
     /// Compiles the invocation of an EVM operator (operands must be already pushed).
-    fn compile_operator(&self, op: &Opcode) -> Instruction<'a> {
+    fn compile_operator(&self, op: &Opcode, pc: usize) -> Result<Instruction<'a>, Diagnostic> {
         let op = op.zeroed();
-        let op_idx = self.op_table.get(&op).unwrap();
-        Instruction::Call(*op_idx)
+        match self.op_table.get(&op) {
+            Some(op_idx) => Ok(Instruction::Call(*op_idx)),
+            None => Err(Diagnostic::new(
+                pc,
+                format!("`{}' is not supported by the runtime library", op),
+            )),
+        }
+    }
+
+    /// Ensures that no function exported so far, by the runtime library or by
+    /// the compiler, has the given name.
+    fn check_method_name(&self, name: &str) -> Result<(), Error> {
+        match self
+            .builder
+            .exports
+            .iter()
+            .any(|export| export.name == name)
+        {
+            true => Err(Error::MethodClash(name.to_string())),
+            false => Ok(()),
+        }
     }
 
     fn emit_function(&mut self, name: Option<String>, mut code: Vec<Instruction>) -> FunctionIndex {
@@ -589,7 +758,7 @@ subgraph cluster_wasm {{ label = \"wasm\"
     }
 }
 
-fn make_op_table(module: &ModuleBuilder) -> HashMap<Opcode, FunctionIndex> {
+fn make_op_table(module: &ModuleBuilder) -> Result<HashMap<Opcode, FunctionIndex>, Error> {
     let mut result: HashMap<Opcode, FunctionIndex> = HashMap::new();
     for export in module.exports.iter() {
         if let Export {
@@ -599,20 +768,24 @@ fn make_op_table(module: &ModuleBuilder) -> HashMap<Opcode, FunctionIndex> {
         } = export
         {
             match name.as_str() {
-                "_abi_buffer" | "_evm_start" | "_evm_init" | "_evm_call" | "_evm_exec"
+                "_abi_buffer" | "_evm_start" | "_evm_init" | "_evm_call" | "_evm_call_value"
+                | "_evm_address_of" | "_evm_migrate" | "_evm_upgrade" | "_evm_exec"
                 | "_evm_post_exec" | "_evm_pop_u32" | "_evm_push_u32" | "_evm_burn_gas"
                 | "_evm_set_pc" | "execute" => {}
                 export_sym => match parse_opcode(&export_sym.to_ascii_uppercase()) {
-                    None => unreachable!(), // TODO
+                    None => {
+                        let message = format!("unknown `{}' function", export_sym);
+                        return Err(Error::Runtime(message));
+                    }
                     Some(op) => _ = result.insert(op, *index),
                 },
             }
         }
     }
-    result
+    Ok(result)
 }
 
-fn find_runtime_function(module: &ModuleBuilder, func_name: &str) -> Option<FunctionIndex> {
+fn find_runtime_function(module: &ModuleBuilder, func_name: &str) -> Result<FunctionIndex, Error> {
     for export in module.exports.iter() {
         if let Export {
             name,
@@ -621,14 +794,14 @@ fn find_runtime_function(module: &ModuleBuilder, func_name: &str) -> Option<Func
         } = export
         {
             if name == func_name {
-                return Some(*index);
+                return Ok(*index);
             }
         }
     }
-    None // not found
+    Err(Error::Runtime(format!("no `{}' function", func_name)))
 }
 
-fn find_abi_buffer(module: &ModuleBuilder) -> Option<DataOffset> {
+fn find_abi_buffer(module: &ModuleBuilder) -> Result<DataOffset, Error> {
     for export in module.exports.iter() {
         if let Export {
             name,
@@ -637,13 +810,198 @@ fn find_abi_buffer(module: &ModuleBuilder) -> Option<DataOffset> {
         } = export
         {
             if name == "_abi_buffer" {
-                let g = module.globals.get(*index as usize).unwrap();
-                match g.init_instr {
-                    wasm_encoder::Instruction::I32Const(off) => return Some(off),
-                    _ => return None,
-                }
+                return match module.globals.get(*index as usize) {
+                    Some(Glob {
+                        init_instr: Instruction::I32Const(off),
+                        ..
+                    }) => Ok(*off),
+                    _ => Err(Error::Runtime("invalid `_abi_buffer' global".to_string())),
+                };
             }
         }
     }
-    None // not found
+    Err(Error::Runtime("no `_abi_buffer' global".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_encoder::GlobalType;
+
+    use super::*;
+    use crate::{abi::parse_str, config::MAX_VALUE_SCALE, decode::decode_bytecode};
+
+    /// Builds a runtime library with an empty function for each function that
+    /// the compiler calls, and for each of the given opcodes.
+    fn runtime(opcodes: &[&str]) -> ModuleBuilder<'static> {
+        let mut runtime = ModuleBuilder::new();
+        runtime.globals.push(Glob {
+            global_type: GlobalType {
+                val_type: ValType::I32,
+                mutable: false,
+            },
+            init_instr: Instruction::I32Const(0x100),
+        });
+        runtime.add_export(Export {
+            name: "_abi_buffer".to_string(),
+            kind: ExportKind::Global,
+            index: 0,
+        });
+        // The compiler takes index 0 to mean a function not found yet:
+        let functions = [
+            "_evm_exec",
+            "_evm_init",
+            "_evm_call",
+            "_evm_call_value",
+            "_evm_address_of",
+            "_evm_migrate",
+            "_evm_upgrade",
+            "_evm_post_exec",
+            "_evm_pop_u32",
+            "_evm_burn_gas",
+            "_evm_set_pc",
+        ];
+        for name in functions.iter().chain(opcodes) {
+            let mut body = Function::new(vec![]);
+            body.instruction(&Instruction::End);
+            let signature = Signature {
+                params: vec![],
+                results: vec![],
+            };
+            let index = runtime.add_function(signature, body);
+            runtime.add_export(Export {
+                name: name.to_string(),
+                kind: ExportKind::Func,
+                index,
+            });
+        }
+        runtime
+    }
+
+    /// Compiles a program that just stops, with an ABI of view functions
+    /// with the given names.
+    fn compile_abi(names: &[&str], config: CompilerConfig) -> Result<Module, Error> {
+        let (program, _) = decode_bytecode("00").unwrap(); // STOP
+        let functions: Vec<_> = names
+            .iter()
+            .map(|name| {
+                format!(
+                    r#"{{"type": "function", "name": "{}", "inputs": [], "outputs": [], "stateMutability": "view"}}"#,
+                    name
+                )
+            })
+            .collect();
+        let abi = parse_str(&format!("[{}]", functions.join(","))).unwrap();
+        compile(&program, None, Some(abi), runtime(&["stop"]), config)
+    }
+
+    fn assert_clash(result: Result<Module, Error>, method: &str) {
+        match result {
+            Err(Error::MethodClash(name)) => assert_eq!(name, method),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_compile_abi() {
+        assert!(compile_abi(&["get", "set"], CompilerConfig::new()).is_ok());
+    }
+
+    #[test]
+    fn test_compile_opcodes() {
+        let (program, _) = decode_bytecode("5f5c5f5d00").unwrap(); // PUSH0 TLOAD PUSH0 TSTORE STOP
+        let opcodes = ["push0", "tload", "tstore", "stop"];
+        let result = compile(
+            &program,
+            None,
+            None,
+            runtime(&opcodes),
+            CompilerConfig::new(),
+        );
+        assert!(result.is_ok());
+
+        let result = compile(
+            &program,
+            None,
+            None,
+            runtime(&opcodes[1..]),
+            CompilerConfig::new(),
+        );
+        match result {
+            Err(Error::Program(diagnostic)) => assert_eq!(diagnostic.offset, 0),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+
+        let result = compile(
+            &program,
+            None,
+            None,
+            runtime(&["mystery"]),
+            CompilerConfig::new(),
+        );
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn test_compile_invalid_jumps() {
+        // PUSH1 0x02 JUMP, as compiled by old versions of solc for `throw`
+        let (program, _) = decode_bytecode("600256").unwrap();
+        let config = CompilerConfig::new();
+        let result = compile(&program, None, None, runtime(&["invalid"]), config);
+        assert!(result.is_ok());
+        match compile(&program, None, None, runtime(&[]), CompilerConfig::new()) {
+            Err(Error::Program(diagnostic)) => assert_eq!(diagnostic.offset, 2),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+
+        // PUSH1 0x01 PUSH1 0x09 JUMPI STOP
+        let (program, _) = decode_bytecode("600160095700").unwrap();
+        let opcodes = ["push1", "invalid", "stop"];
+        let result = compile(
+            &program,
+            None,
+            None,
+            runtime(&opcodes),
+            CompilerConfig::new(),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_value_scale() {
+        let config = CompilerConfig::new().value_scale(MAX_VALUE_SCALE);
+        assert!(compile_abi(&[], config).is_ok());
+        let config = CompilerConfig::new().value_scale(MAX_VALUE_SCALE + 1);
+        assert!(matches!(
+            compile_abi(&[], config),
+            Err(Error::ValueScale(25))
+        ));
+    }
+
+    #[test]
+    fn test_method_clash() {
+        let result = compile_abi(&["evm_address_of"], CompilerConfig::new());
+        assert_clash(result, "evm_address_of");
+        assert_clash(compile_abi(&["get", "get"], CompilerConfig::new()), "get");
+        assert_clash(compile_abi(&["stop"], CompilerConfig::new()), "stop");
+    }
+
+    #[test]
+    fn test_migrate() {
+        assert!(compile_abi(&["migrate"], CompilerConfig::new()).is_ok());
+        let result = compile_abi(&["evm_migrate"], CompilerConfig::new());
+        assert_clash(result, "evm_migrate");
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let config = CompilerConfig::new().upgrade_owner("alice.near");
+        assert!(compile_abi(&["upgrade"], config.clone()).is_ok());
+        assert_clash(compile_abi(&["evm_upgrade"], config), "evm_upgrade");
+
+        let config = CompilerConfig::new().upgrade_owner("Alice.near");
+        match compile_abi(&[], config) {
+            Err(Error::AccountId(owner)) => assert_eq!(owner, "Alice.near"),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+    }
 }
@@ -1,35 +1,164 @@
 // This is free and unencumbered software released into the public domain.
 
-use std::{fs, path::PathBuf};
+use std::{num::ParseIntError, path::PathBuf};
 
+use crate::{error::Error, format::OutputABI};
+
+/// The chain ID of Aurora, the EVM on NEAR mainnet.
+pub const MAINNET_CHAIN_ID: u64 = 1313161554;
+pub const TESTNET_CHAIN_ID: u64 = 1313161555;
+pub const BETANET_CHAIN_ID: u64 = 1313161556;
+
+/// The largest value scale, at which one NEAR is one wei.
+pub const MAX_VALUE_SCALE: u8 = 24;
+
+/// Parses a chain ID, given either as a number or as the name of a network.
+pub fn parse_chain_id(chain_id: &str) -> Result<u64, ParseIntError> {
+    match chain_id {
+        "mainnet" => Ok(MAINNET_CHAIN_ID),
+        "testnet" => Ok(TESTNET_CHAIN_ID),
+        "betanet" => Ok(BETANET_CHAIN_ID),
+        _ => chain_id.parse(),
+    }
+}
+
+/// Whether the given string is a valid NEAR account ID: 2 to 64 characters,
+/// in parts of lowercase letters and digits separated by `.`, `-` or `_`.
+pub fn is_valid_account_id(account_id: &str) -> bool {
+    (2..=64).contains(&account_id.len())
+        && account_id.split('.').all(|part| {
+            part.split(['-', '_']).all(|word| {
+                !word.is_empty()
+                    && word
+                        .bytes()
+                        .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit())
+            })
+        })
+}
+
+/// How to compile a contract, built up from the defaults like so:
+///
+/// ```
+/// # use evm2near::{CompilerConfig, OutputABI};
+/// let config = CompilerConfig::new()
+///     .output_abi(OutputABI::Wasi)
+///     .value_scale(6);
+/// ```
+#[derive(Clone, Debug)]
 pub struct CompilerConfig {
-    pub debug_path: Option<PathBuf>,
-    pub optimize_level: u8,
-    pub gas_accounting: bool,
-    pub program_counter: bool,
-    pub chain_id: u64,
+    pub(crate) output_abi: OutputABI,
+    pub(crate) debug_path: Option<PathBuf>,
+    pub(crate) optimize_level: u8,
+    pub(crate) gas_accounting: bool,
+    pub(crate) program_counter: bool,
+    pub(crate) chain_id: u64,
+    pub(crate) value_scale: u8,
+    /// The account allowed to upgrade the contract, if it is upgradable
+    pub(crate) upgrade_owner: Option<String>,
+}
+
+impl Default for CompilerConfig {
+    fn default() -> Self {
+        CompilerConfig {
+            output_abi: OutputABI::Near,
+            debug_path: None,
+            optimize_level: 0,
+            gas_accounting: true,
+            program_counter: true,
+            chain_id: MAINNET_CHAIN_ID,
+            value_scale: 0,
+            upgrade_owner: None,
+        }
+    }
 }
 
 impl CompilerConfig {
-    pub fn new(
-        debug_path: Option<PathBuf>,
-        optimize_level: u8,
-        gas_accounting: bool,
-        program_counter: bool,
-        chain_id: u64,
-    ) -> Self {
-        if let Some(debug_dir) = &debug_path {
-            if fs::read_dir(debug_dir).is_ok() {
-                fs::remove_dir_all(debug_dir).expect("unable to remove previous debug directory!");
-            }
-            fs::create_dir_all(debug_dir).expect("unable to create debug directory!");
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to compile for NEAR, which is the default, or for WASI.
+    pub fn output_abi(mut self, output_abi: OutputABI) -> Self {
+        self.output_abi = output_abi;
+        self
+    }
+
+    /// Writes the intermediate stages of compilation to the given directory,
+    /// which is emptied first.
+    pub fn debug_path(mut self, debug_path: impl Into<PathBuf>) -> Self {
+        self.debug_path = Some(debug_path.into());
+        self
+    }
+
+    pub fn optimize_level(mut self, optimize_level: u8) -> Self {
+        self.optimize_level = optimize_level;
+        self
+    }
+
+    /// Whether to account for EVM gas precisely, which is on by default.
+    pub fn gas_accounting(mut self, gas_accounting: bool) -> Self {
+        self.gas_accounting = gas_accounting;
+        self
+    }
+
+    /// Whether to keep the EVM program counter up to date, which is on by default.
+    pub fn program_counter(mut self, program_counter: bool) -> Self {
+        self.program_counter = program_counter;
+        self
+    }
+
+    /// The value of CHAINID, which is Aurora's mainnet by default.
+    pub fn chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// The number of decimal places dropped when converting yoctoNEAR to wei,
+    /// up to `MAX_VALUE_SCALE`, which is checked when compiling.
+    pub fn value_scale(mut self, value_scale: u8) -> Self {
+        self.value_scale = value_scale;
+        self
+    }
+
+    /// Makes the contract upgradable by the given NEAR account, whose ID is
+    /// checked when compiling (see `is_valid_account_id`).
+    pub fn upgrade_owner(mut self, upgrade_owner: impl Into<String>) -> Self {
+        self.upgrade_owner = Some(upgrade_owner.into());
+        self
+    }
+
+    /// Checks the settings that the builder methods take as given.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if self.value_scale > MAX_VALUE_SCALE {
+            return Err(Error::ValueScale(self.value_scale));
         }
-        CompilerConfig {
-            debug_path,
-            optimize_level,
-            gas_accounting,
-            program_counter,
-            chain_id,
+        match &self.upgrade_owner {
+            Some(owner) if !is_valid_account_id(owner) => Err(Error::AccountId(owner.clone())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_id() {
+        for account_id in ["alice.near", "a-b_c.testnet", "0x12ab", "near", "aa"] {
+            assert!(is_valid_account_id(account_id), "{}", account_id);
+        }
+        let long = "a".repeat(65);
+        for account_id in [
+            "Alice.near",
+            "a",
+            "a..near",
+            ".near",
+            "alice-.near",
+            "a b",
+            &long,
+        ] {
+            assert!(!is_valid_account_id(account_id), "{}", account_id);
         }
     }
 }
@@ -1,23 +1,81 @@
 // This is free and unencumbered software released into the public domain.
 
-use evm_rs::{decode_program, DecodeError, Program};
+use evm_rs::DecodeError;
 
-pub fn decode_bytecode(input: &str) -> Result<Program, DecodeError> {
-    let input = input.trim();
+use crate::{
+    metadata::{split_metadata, Metadata},
+    opcode::{decode_program, Program},
+};
+
+/// Decodes bytecode given as hexadecimal text, which may be wrapped over several
+/// lines as by `xxd -p`, leaving out the metadata at its end, which is returned
+/// separately.
+pub fn decode_bytecode(input: &str) -> Result<(Program, Option<Metadata>), DecodeError> {
+    let input: String = input.split_ascii_whitespace().collect();
     let input = if input.starts_with("0x") || input.starts_with("0X") {
         &input[2..]
     } else {
-        input
-    };
-    let input = match input.find("a164736f6c63") {
-        Some(n) => &input[..n],
-        None => input,
+        &input
     };
     match hex::decode(input) {
         Err(_err) => Err(DecodeError::InvalidBytecode),
-        Ok(bytecode) => match decode_program(bytecode.as_slice()) {
-            Err(err) => Err(err),
-            Ok(program) => Ok(program),
-        },
+        Ok(bytecode) => {
+            let (code, metadata) = split_metadata(&bytecode);
+            match decode_program(code) {
+                Err(err) => Err(err),
+                Ok(program) => Ok((program, metadata)),
+            }
+        }
+    }
+}
+
+/// Decodes bytecode given either as hexadecimal text or as raw binary,
+/// which is never valid hexadecimal since opcodes like PUSH1 (0x60) are not.
+pub fn decode_bytecode_bytes(input: &[u8]) -> Result<(Program, Option<Metadata>), DecodeError> {
+    match std::str::from_utf8(input) {
+        Ok(text) if is_hex(text) => decode_bytecode(text),
+        _ => decode_bytecode(&hex::encode(input)),
+    }
+}
+
+fn is_hex(text: &str) -> bool {
+    let text = text.trim_start();
+    let text = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    text.bytes()
+        .all(|b| b.is_ascii_hexdigit() || b.is_ascii_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_bytecode_bytes() {
+        let (program, _) = decode_bytecode("6080604052").unwrap();
+        let inputs: [&[u8]; 5] = [
+            b"6080604052\n",
+            b"0x6080604052",
+            b"608060\n4052\n", // as by `xxd -p`
+            b" 0x6080 6040\r\n52\t",
+            &[0x60, 0x80, 0x60, 0x40, 0x52],
+        ];
+        for input in inputs {
+            let (decoded, metadata) = decode_bytecode_bytes(input).unwrap();
+            assert!(decoded.0 == program.0);
+            assert_eq!(metadata, None);
+        }
+        let (decoded, _) = decode_bytecode_bytes(b"").unwrap();
+        assert!(decoded.0.is_empty());
+    }
+
+    #[test]
+    fn test_decode_metadata() {
+        let (program, _) = decode_bytecode("6080").unwrap();
+        let (decoded, metadata) = decode_bytecode("6080a164736f6c6343000810000a").unwrap();
+        assert!(decoded.0 == program.0);
+        assert_eq!(metadata.unwrap().solc.as_deref(), Some("0.8.16"));
     }
 }
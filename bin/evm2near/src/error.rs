@@ -1,30 +1,40 @@
 // This is free and unencumbered software released into the public domain.
 
 use evm_rs::DecodeError;
-use std::fmt;
+use std::{fmt, io, path::PathBuf};
 
+use crate::{artifact::ArtifactError, config::MAX_VALUE_SCALE, storage_layout::LayoutError};
+
+/// Why a frontend could not compile source code to EVM bytecode.
 #[derive(Debug, Clone, PartialEq)]
-pub enum CompileError {
+pub enum FrontendError {
     ProgramSpawn,
     ProgramWait,
     Decode(DecodeError),
     UnexpectedOutput,
     UnexpectedExit(i32, Vec<u8>),
     UnexpectedSignal(Vec<u8>),
+    Diagnostics(String),
+    /// There are several contracts to choose from, or none at all
+    AmbiguousContract(Vec<String>),
+    UnknownContract(String, Vec<String>),
+    /// A Yul object with several sub-objects, none of which is known to be deployed
+    AmbiguousObject,
+    /// The compiler version, and the minimum version required
+    UnsupportedVersion(String, String),
 }
 
-#[cfg(feature = "std")]
-impl std::error::Error for CompileError {}
+impl std::error::Error for FrontendError {}
 
-impl CompileError {
+impl FrontendError {
     pub fn with_program(&self, name: &str) -> String {
         self.to_string().replace("%s", name)
     }
 }
 
-impl fmt::Display for CompileError {
+impl fmt::Display for FrontendError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use CompileError::*;
+        use FrontendError::*;
         match self {
             ProgramSpawn => write!(f, "could not execute `%s'"),
             ProgramWait => write!(f, "could not wait for `%s'"),
@@ -45,6 +55,187 @@ impl fmt::Display for CompileError {
                     &String::from_utf8_lossy(stderr).trim_end()
                 )
             }
+            Diagnostics(errors) => write!(f, "errors from `%s':\n\n{}", errors.trim_end()),
+            AmbiguousContract(names) if names.is_empty() => {
+                write!(f, "no contract to deploy in the input")
+            }
+            AmbiguousContract(names) => write!(
+                f,
+                "several contracts in the input, choose one with `--contract': {}",
+                names.join(", ")
+            ),
+            UnknownContract(name, names) if names.is_empty() => {
+                write!(f, "no contract `{}' to deploy in the input", name)
+            }
+            UnknownContract(name, names) => write!(
+                f,
+                "no contract `{}' to deploy in the input, choose one of: {}",
+                name,
+                names.join(", ")
+            ),
+            AmbiguousObject => write!(
+                f,
+                "the Yul object has several sub-objects, so its runtime code is unknown"
+            ),
+            UnsupportedVersion(version, min_version) => write!(
+                f,
+                "`%s' version {} is not supported, use `--solc' to choose version {} or later",
+                version, min_version
+            ),
+        }
+    }
+}
+
+/// A problem with the EVM program, at the given offset in its bytecode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(offset: usize, message: String) -> Self {
+        Self { offset, message }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at offset 0x{:x}: {}", self.offset, self.message)
+    }
+}
+
+/// Why EVM bytecode could not be compiled to WebAssembly.
+#[derive(Debug)]
+pub enum Error {
+    Decode(DecodeError),
+    Program(Diagnostic),
+    /// An ABI type that the runtime cannot encode, and the function using it
+    AbiType(String, String),
+    /// The size of the ABI data, which does not fit in the runtime's ABI buffer
+    AbiOverflow(usize),
+    /// The name of an ABI function that another method already has
+    MethodClash(String),
+    /// An invalid NEAR account ID given as the upgrade owner
+    AccountId(String),
+    /// A value scale above `MAX_VALUE_SCALE`
+    ValueScale(u8),
+    Runtime(String),
+    /// The runtime library is not a WebAssembly module that can be translated
+    RuntimeModule(TranslateError),
+    /// The frontend's compiler program, and why it failed
+    Frontend(String, FrontendError),
+    Artifact(ArtifactError),
+    /// An input file that could not be read
+    Read(PathBuf, io::Error),
+    /// An artifact that is not text
+    Utf8(PathBuf),
+    Abi(PathBuf, serde_json::Error),
+    /// The ABI functions that the bytecode does not dispatch
+    Undispatched(Vec<String>),
+    StorageLayout(PathBuf, serde_json::Error),
+    /// Checking upgrade compatibility requires the storage layout of the contract
+    NoStorageLayout,
+    /// The contract to upgrade from, and why the contract cannot upgrade it
+    Upgrade(PathBuf, LayoutError),
+    Debug(PathBuf, io::Error),
+}
+
+impl std::error::Error for Error {}
+
+impl From<DecodeError> for Error {
+    fn from(err: DecodeError) -> Self {
+        Error::Decode(err)
+    }
+}
+
+impl From<TranslateError> for Error {
+    fn from(err: TranslateError) -> Self {
+        Error::RuntimeModule(err)
+    }
+}
+
+impl From<ArtifactError> for Error {
+    fn from(err: ArtifactError) -> Self {
+        Error::Artifact(err)
+    }
+}
+
+impl From<Diagnostic> for Error {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Error::Program(diagnostic)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            Decode(err) => write!(f, "invalid bytecode: {}", err),
+            Program(diagnostic) => write!(f, "{}", diagnostic),
+            AbiType(r#type, func) => {
+                write!(f, "unknown ABI type `{}' in function `{}'", r#type, func)
+            }
+            AbiOverflow(len) => write!(f, "{} bytes of ABI data do not fit in the runtime", len),
+            MethodClash(name) => write!(f, "two methods are named `{}'", name),
+            AccountId(account_id) => write!(f, "invalid NEAR account ID `{}'", account_id),
+            ValueScale(scale) => write!(
+                f,
+                "value scale {} is above the maximum of {}",
+                scale, MAX_VALUE_SCALE
+            ),
+            Runtime(err) => write!(f, "invalid runtime library: {}", err),
+            RuntimeModule(err) => write!(f, "invalid runtime library: {}", err),
+            Frontend(program, err) => write!(f, "{}", err.with_program(program)),
+            Artifact(err) => write!(f, "{}", err),
+            Read(path, err) => write!(f, "could not read `{}': {}", path.display(), err),
+            Utf8(path) => write!(f, "`{}' is not valid UTF-8", path.display()),
+            Abi(path, err) => write!(f, "could not parse ABI `{}': {}", path.display(), err),
+            Undispatched(functions) => write!(
+                f,
+                "the ABI does not match the contract, which does not dispatch {}",
+                functions.join(", ")
+            ),
+            StorageLayout(path, err) => {
+                write!(
+                    f,
+                    "could not parse storage layout `{}': {}",
+                    path.display(),
+                    err
+                )
+            }
+            NoStorageLayout => write!(f, "checking upgrade compatibility requires Solidity input"),
+            Upgrade(path, err) => write!(f, "cannot upgrade `{}': {}", path.display(), err),
+            Debug(path, err) => write!(f, "could not write `{}': {}", path.display(), err),
+        }
+    }
+}
+
+/// Why a WebAssembly module could not be translated for the compiler to extend.
+#[derive(Debug)]
+pub enum TranslateError {
+    Parse(wasmparser::BinaryReaderError),
+    /// A malformed part of the module
+    Invalid(&'static str),
+    /// A feature of WebAssembly that NEAR doesn't support, or the translation
+    Unsupported(&'static str),
+}
+
+impl std::error::Error for TranslateError {}
+
+impl From<wasmparser::BinaryReaderError> for TranslateError {
+    fn from(err: wasmparser::BinaryReaderError) -> Self {
+        TranslateError::Parse(err)
+    }
+}
+
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TranslateError::*;
+        match self {
+            Parse(err) => write!(f, "{}", err),
+            Invalid(message) => write!(f, "{}", message),
+            Unsupported(message) => write!(f, "{}", message),
         }
     }
 }
@@ -1,10 +1,18 @@
 // This is free and unencumbered software released into the public domain.
 
-#[derive(clap::ValueEnum, Clone, Debug)]
+use serde::Deserialize;
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
 pub enum InputFormat {
     Auto,
     Bin,
     Sol,
+    Vyper,
+    Yul,
+    Foundry,
+    Hardhat,
+    /// The output of `solc --standard-json`
+    SolcJson,
 }
 
 #[derive(clap::ValueEnum, Clone, Debug)]
@@ -14,7 +22,8 @@ pub enum OutputFormat {
     Wat,
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
+#[derive(clap::ValueEnum, Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputABI {
     Near,
     Wasi,
@@ -27,16 +36,26 @@ pub fn parse_input_format(format: &str) -> Option<InputFormat> {
         "auto" => Auto,
         "bytecode" | "bin" | "hex" => Bin,
         "solidity" | "sol" => Sol,
+        "vyper" | "vy" => Vyper,
+        "yul" => Yul,
+        "foundry" => Foundry,
+        "hardhat" => Hardhat,
+        "solc-json" => SolcJson,
         _ => return None,
     };
     Some(result)
 }
 
-pub fn parse_input_extension(extension: Option<&str>) -> Option<InputFormat> {
+/// Determines the input format from the file extension, or else from the
+/// contents, which tell the kind of a JSON artifact.
+pub fn parse_input_extension(extension: Option<&str>, input: &str) -> Option<InputFormat> {
     use InputFormat::*;
     let result = match extension.unwrap_or_default() {
         "bin" | "hex" => Bin,
         "sol" => Sol,
+        "vy" => Vyper,
+        "yul" => Yul,
+        _ if input.trim_start().starts_with('{') => return crate::artifact::detect(input),
         _ => return None,
     };
     Some(result)
@@ -0,0 +1,72 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    abi::Functions,
+    error::{Error, FrontendError},
+    metadata::Metadata,
+    opcode::Program,
+    storage_layout::StorageLayout,
+};
+
+/// A contract compiled from source code to EVM bytecode.
+pub struct Contract {
+    pub name: String,
+    pub program: Program,
+    pub metadata: Option<Metadata>,
+    pub abi: Functions,
+    /// Only known for Solidity contracts
+    pub storage_layout: Option<StorageLayout>,
+}
+
+/// A compiler from some source language to EVM bytecode, such as `solc`.
+pub trait Frontend {
+    /// The name of the source language, for messages
+    fn language(&self) -> &'static str;
+
+    /// The name of the compiler program, for messages
+    fn program(&self) -> String;
+
+    /// Compiles the given source file, failing with an error that refers to
+    /// the compiler program as `%s`.
+    fn compile_source(&self, input_path: &Path) -> Result<Contract, FrontendError>;
+
+    fn compile(&self, input_path: &Path) -> Result<Contract, Error> {
+        self.compile_source(input_path)
+            .map_err(|err| Error::Frontend(self.program(), err))
+    }
+}
+
+/// The name of a contract compiled from a file which defines only that contract.
+pub fn contract_name(input_path: &Path) -> String {
+    input_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Runs the compiler program and returns what it printed to stdout.
+pub fn execute(mut command: Command) -> Result<Vec<u8>, FrontendError> {
+    let subprocess = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let child = match subprocess {
+        Err(_err) => return Err(FrontendError::ProgramSpawn),
+        Ok(child) => child,
+    };
+    let output = match child.wait_with_output() {
+        Err(_) => return Err(FrontendError::ProgramWait),
+        Ok(output) => output,
+    };
+    match output.status.code() {
+        Some(0) => Ok(output.stdout),
+        Some(code) => Err(FrontendError::UnexpectedExit(code, output.stderr)),
+        None => Err(FrontendError::UnexpectedSignal(output.stderr)),
+    }
+}
@@ -0,0 +1,327 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Reads contracts from input files in any of the input formats, compiling
+//! source code to EVM bytecode, and checks them before they are compiled.
+//!
+//! ```no_run
+//! # use evm2near::{input::{read_input, InputConfig}, CompilerConfig};
+//! let input = read_input("src/Counter.sol".as_ref(), &InputConfig::new())?;
+//! let wasm = evm2near::compile_program(
+//!     &input.program,
+//!     input.metadata.as_ref(),
+//!     input.abi,
+//!     &CompilerConfig::new(),
+//! )?;
+//! # Ok::<(), evm2near::Error>(())
+//! ```
+
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    abi::{self, Functions},
+    artifact,
+    decode::decode_bytecode_bytes,
+    error::Error,
+    format::{parse_input_extension, InputFormat},
+    frontend::Frontend,
+    manifest::SolcSettings,
+    metadata::Metadata,
+    opcode::Program,
+    solidity::{EvmVersion, Solc, Solidity},
+    storage_layout::{self, StorageLayout},
+    vyper::Vyper,
+    yul::Yul,
+};
+
+/// How to read an input file, and compile it if it is source code.
+#[derive(Clone, Debug)]
+pub struct InputConfig {
+    /// The input format, which is detected from the file by default
+    pub format: InputFormat,
+    pub solc: Solc,
+    pub evm_version: EvmVersion,
+    /// The contract to compile, if the input defines several
+    pub contract: Option<String>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        let solc = SolcSettings::default();
+        InputConfig {
+            format: InputFormat::Auto,
+            solc: solc.solc(),
+            evm_version: solc.evm_version(),
+            contract: None,
+        }
+    }
+}
+
+impl InputConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A contract read from an input file.
+pub struct Input {
+    /// The name of the contract, if it was compiled from source code
+    pub name: Option<String>,
+    pub program: Program,
+    pub metadata: Option<Metadata>,
+    pub abi: Option<Functions>,
+    /// Only known for Solidity contracts
+    pub storage_layout: Option<StorageLayout>,
+}
+
+/// Returns the input file, or if it is a directory, each file in it or its
+/// subdirectories that is in the given format (or any recognized format,
+/// for `InputFormat::Auto`). Artifacts without deployed bytecode, which
+/// build tools output for interfaces and abstract contracts, are skipped.
+pub fn find_inputs(path: &Path, format: &InputFormat) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = Vec::new();
+    find_inputs_in(path, format, &mut paths)?;
+    Ok(paths)
+}
+
+fn find_inputs_in(
+    path: &Path,
+    format: &InputFormat,
+    paths: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+    if !path.is_dir() {
+        paths.push(path.to_path_buf());
+        return Ok(());
+    }
+    let entries = fs::read_dir(path).and_then(|dir| {
+        dir.map(|entry| Ok(entry?.path()))
+            .collect::<io::Result<Vec<_>>>()
+    });
+    let mut entries = entries.map_err(|err| Error::Read(path.to_path_buf(), err))?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            find_inputs_in(&entry, format, paths)?;
+            continue;
+        }
+        let extension = entry.extension().and_then(OsStr::to_str);
+        // Only JSON files need to be read, to tell artifacts from other JSON
+        let text = match extension {
+            Some("json") => fs::read_to_string(&entry).unwrap_or_default(),
+            _ => String::new(),
+        };
+        let entry_format = parse_input_extension(extension, &text);
+        let wanted = match format {
+            InputFormat::Auto => entry_format.is_some(),
+            format => entry_format.as_ref() == Some(format),
+        };
+        let deployable = match entry_format {
+            Some(InputFormat::Foundry | InputFormat::Hardhat) => artifact::is_deployable(&text),
+            _ => true,
+        };
+        if wanted && deployable {
+            paths.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the contract from the input file, compiling it if it is source code.
+pub fn read_input(path: &Path, config: &InputConfig) -> Result<Input, Error> {
+    let bytes = fs::read(path).map_err(|err| Error::Read(path.to_path_buf(), err))?;
+    parse_input(path, &bytes, config)
+}
+
+/// Reads the contract from the contents of the input file, which is only
+/// read again to compile source code.
+pub fn parse_input(path: &Path, bytes: &[u8], config: &InputConfig) -> Result<Input, Error> {
+    // Bytecode may be binary, but any other input must be text
+    let text = std::str::from_utf8(bytes).ok();
+
+    let extension = path.extension().and_then(OsStr::to_str);
+    let format = match &config.format {
+        InputFormat::Auto => {
+            parse_input_extension(extension, text.unwrap_or_default()).unwrap_or(InputFormat::Bin)
+        }
+        format => format.clone(),
+    };
+
+    match format {
+        InputFormat::Auto | InputFormat::Bin => {
+            let (program, metadata) = decode_bytecode_bytes(bytes)?;
+            Ok(Input {
+                name: None,
+                program,
+                metadata,
+                abi: None,
+                storage_layout: None,
+            })
+        }
+        InputFormat::Sol | InputFormat::Vyper | InputFormat::Yul => {
+            let frontend: Box<dyn Frontend> = match format {
+                InputFormat::Sol => Box::new(Solidity {
+                    solc: config.solc.clone(),
+                    evm_version: config.evm_version,
+                    contract_name: config.contract.clone(),
+                }),
+                InputFormat::Vyper => Box::new(Vyper {
+                    evm_version: config.evm_version,
+                }),
+                _ => Box::new(Yul {
+                    solc: config.solc.clone(),
+                    evm_version: config.evm_version,
+                }),
+            };
+            let contract = frontend.compile(path)?;
+            Ok(Input {
+                name: Some(contract.name),
+                program: contract.program,
+                metadata: contract.metadata,
+                abi: Some(contract.abi),
+                storage_layout: contract.storage_layout,
+            })
+        }
+        InputFormat::Foundry | InputFormat::Hardhat | InputFormat::SolcJson => {
+            let text = text.ok_or_else(|| Error::Utf8(path.to_path_buf()))?;
+            let artifact = match format {
+                InputFormat::Foundry => artifact::parse_foundry(text),
+                InputFormat::Hardhat => artifact::parse_hardhat(text),
+                _ => artifact::parse_solc_json(text, config.contract.as_deref()),
+            }?;
+            Ok(Input {
+                name: None,
+                program: artifact.program,
+                metadata: artifact.metadata,
+                abi: Some(artifact.abi),
+                storage_layout: None,
+            })
+        }
+    }
+}
+
+/// Reads a JSON ABI, such as one output by `solc --abi`.
+pub fn read_abi(path: &Path) -> Result<Functions, Error> {
+    let json = fs::read(path).map_err(|err| Error::Read(path.to_path_buf(), err))?;
+    abi::parse_bytes(&json).map_err(|err| Error::Abi(path.to_path_buf(), err))
+}
+
+impl Input {
+    /// Checks that the bytecode dispatches every function of the ABI, if any.
+    pub fn check_abi(&self) -> Result<(), Error> {
+        let abi = match &self.abi {
+            Some(abi) => abi,
+            None => return Ok(()),
+        };
+        let undispatched: Vec<String> = abi
+            .undispatched(&self.program)
+            .into_iter()
+            .map(|func| {
+                format!(
+                    "`{}' (selector 0x{})",
+                    func,
+                    hex::encode(func.selector_bytes())
+                )
+            })
+            .collect();
+        match undispatched.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Undispatched(undispatched)),
+        }
+    }
+
+    /// Checks that the contract can upgrade the old one, given as Solidity
+    /// code or as a `solc --storage-layout` JSON file.
+    pub fn check_upgrade(&self, old_path: &Path, config: &InputConfig) -> Result<(), Error> {
+        let new_layout = self.storage_layout.as_ref().ok_or(Error::NoStorageLayout)?;
+        let old_layout = match old_path.extension().and_then(OsStr::to_str) {
+            Some("sol") => {
+                let frontend = Solidity {
+                    solc: config.solc.clone(),
+                    evm_version: config.evm_version,
+                    contract_name: config.contract.clone(),
+                };
+                // Solidity contracts always have a storage layout
+                frontend
+                    .compile(old_path)?
+                    .storage_layout
+                    .unwrap_or_default()
+            }
+            _ => {
+                let json = fs::read_to_string(old_path)
+                    .map_err(|err| Error::Read(old_path.to_path_buf(), err))?;
+                storage_layout::parse_str(&json)
+                    .map_err(|err| Error::StorageLayout(old_path.to_path_buf(), err))?
+            }
+        };
+        old_layout
+            .check_upgrade(new_layout)
+            .map_err(|err| Error::Upgrade(old_path.to_path_buf(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABI: &str = r#"[
+        {"type": "function", "name": "get", "inputs": [], "outputs": [], "stateMutability": "view"},
+        {"type": "function", "name": "set", "inputs": [], "outputs": [], "stateMutability": "view"}
+    ]"#;
+
+    #[test]
+    fn test_parse_input() {
+        // PUSH4 0x6d4ce63c (the selector of `get()`) STOP
+        let input =
+            parse_input("Counter.bin".as_ref(), b"636d4ce63c00", &InputConfig::new()).unwrap();
+        assert_eq!(input.program.0.len(), 2);
+        assert!(input.abi.is_none());
+
+        let config = InputConfig {
+            format: InputFormat::Foundry,
+            ..InputConfig::new()
+        };
+        let result = parse_input("Counter.json".as_ref(), b"636d4ce63c00", &config);
+        assert!(matches!(result, Err(Error::Artifact(_))));
+    }
+
+    #[test]
+    fn test_find_inputs() {
+        let dir = std::env::temp_dir().join(format!("evm2near-find-inputs-{}", std::process::id()));
+        fs::create_dir_all(dir.join("Counter.sol")).unwrap();
+        let counter = r#"{"abi": [], "deployedBytecode": {"object": "0x6080604052"}}"#;
+        let interface = r#"{"abi": [], "deployedBytecode": {"object": "0x"}}"#;
+        fs::write(dir.join("Counter.sol/Counter.json"), counter).unwrap();
+        fs::write(dir.join("Counter.sol/ICounter.json"), interface).unwrap();
+        fs::write(dir.join("README.md"), "").unwrap();
+
+        let paths = find_inputs(&dir, &InputFormat::Auto);
+        let foundry_paths = find_inputs(&dir, &InputFormat::Foundry);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(paths.unwrap(), vec![dir.join("Counter.sol/Counter.json")]);
+        assert_eq!(
+            foundry_paths.unwrap(),
+            vec![dir.join("Counter.sol/Counter.json")]
+        );
+    }
+
+    #[test]
+    fn test_check_abi() {
+        let mut input =
+            parse_input("Counter.bin".as_ref(), b"636d4ce63c00", &InputConfig::new()).unwrap();
+        assert!(input.check_abi().is_ok());
+        input.abi = Some(abi::parse_str(ABI).unwrap());
+        match input.check_abi() {
+            Err(Error::Undispatched(functions)) => {
+                assert_eq!(functions, vec!["`set()' (selector 0xb8e010de)"])
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(matches!(
+            input.check_upgrade("Old.json".as_ref(), &InputConfig::new()),
+            Err(Error::NoStorageLayout)
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Compiles EVM bytecode to WebAssembly, for NEAR contracts or WASI programs.
+//!
+//! ```no_run
+//! let bytecode = std::fs::read("Counter.bin")?;
+//! let abi = evm2near::abi::parse_bytes(&std::fs::read("Counter.abi")?)?;
+//! let config = evm2near::CompilerConfig::new().chain_id(1313161555);
+//! let wasm = evm2near::compile(&bytecode, Some(abi), &config)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#![feature(map_first_last)]
+
+pub mod abi;
+mod analyze;
+pub mod artifact;
+mod compile;
+pub mod config;
+pub mod decode;
+mod encode;
+pub mod error;
+pub mod format;
+pub mod frontend;
+pub mod input;
+pub mod manifest;
+pub mod metadata;
+pub mod opcode;
+pub mod solidity;
+pub mod storage_layout;
+pub mod vyper;
+mod wasm_translate;
+pub mod yul;
+
+use crate::wasm_translate::ModuleBuilder;
+pub use crate::{
+    abi::Functions,
+    config::CompilerConfig,
+    error::{Diagnostic, Error},
+    format::OutputABI,
+    metadata::Metadata,
+    opcode::Program,
+};
+
+/// The runtime library of NEAR contracts, which implements the EVM opcodes.
+pub const RUNTIME_NEAR: &[u8] = include_bytes!("../../../evmlib.wasm");
+
+/// The runtime library of WASI programs, which implements the EVM opcodes.
+pub const RUNTIME_WASI: &[u8] = include_bytes!("../../../evmlib.wasi");
+
+/// Compiles EVM runtime bytecode, given as hexadecimal text or as raw bytes,
+/// to a WebAssembly module. If an ABI is given, the module has a method for
+/// each of its functions, which takes its arguments as JSON.
+pub fn compile(
+    input: &[u8],
+    abi: Option<Functions>,
+    config: &CompilerConfig,
+) -> Result<Vec<u8>, Error> {
+    let (program, metadata) = decode::decode_bytecode_bytes(input)?;
+    compile_program(&program, metadata.as_ref(), abi, config)
+}
+
+/// Compiles an EVM program that has already been decoded to a WebAssembly module.
+pub fn compile_program(
+    program: &Program,
+    metadata: Option<&Metadata>,
+    abi: Option<Functions>,
+    config: &CompilerConfig,
+) -> Result<Vec<u8>, Error> {
+    Runtime::new(&config.output_abi)?.compile(program, metadata, abi, config)
+}
+
+/// A runtime library parsed once, to compile any number of contracts with.
+pub struct Runtime {
+    output_abi: OutputABI,
+    library: ModuleBuilder<'static>,
+}
+
+impl Runtime {
+    pub fn new(output_abi: &OutputABI) -> Result<Self, Error> {
+        let runtime = match output_abi {
+            OutputABI::Near => RUNTIME_NEAR,
+            OutputABI::Wasi => RUNTIME_WASI,
+        };
+        let library = wasm_translate::parse(runtime)?;
+        Ok(Runtime {
+            output_abi: output_abi.clone(),
+            library,
+        })
+    }
+
+    /// Compiles an EVM program to a WebAssembly module, for the output ABI
+    /// that this runtime library was parsed for.
+    pub fn compile(
+        &self,
+        program: &Program,
+        metadata: Option<&Metadata>,
+        abi: Option<Functions>,
+        config: &CompilerConfig,
+    ) -> Result<Vec<u8>, Error> {
+        if config.output_abi != self.output_abi {
+            return Err(Error::Runtime(format!(
+                "parsed for {:?}, not {:?}",
+                self.output_abi, config.output_abi
+            )));
+        }
+        let module =
+            compile::compile(program, metadata, abi, self.library.clone(), config.clone())?;
+        Ok(module.finish())
+    }
+}
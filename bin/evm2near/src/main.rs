@@ -1,32 +1,26 @@
 // This is free and unencumbered software released into the public domain.
 
-#![feature(map_first_last)]
-
-mod abi;
-mod analyze;
-mod compile;
-mod config;
-mod decode;
-mod encode;
-mod error;
-mod format;
-mod solidity;
-mod wasm_translate;
-
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueSource};
 use std::{
-    ffi::OsStr,
-    fs::{File, OpenOptions},
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
     io::{stdin, stdout, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
 };
 
-use crate::{
-    compile::compile,
-    config::CompilerConfig,
-    decode::decode_bytecode,
-    format::{parse_input_extension, InputFormat, OutputABI, OutputFormat},
-    solidity::SOLC,
+use evm2near::{
+    config::parse_chain_id,
+    format::{InputFormat, OutputABI, OutputFormat},
+    frontend::contract_name,
+    input::{self, InputConfig},
+    manifest::{Manifest, Settings, SolcSettings, MANIFEST},
+    solidity::{EvmVersion, SOLC},
+    CompilerConfig, Error, Functions, Metadata, Program, Runtime,
 };
 
 #[derive(Parser, Debug)]
@@ -45,6 +39,38 @@ struct Options {
     #[clap(short = 'f', long, value_parser, default_value = "auto")]
     from: InputFormat,
 
+    /// The EVM version to compile source code for
+    #[clap(value_name = "VERSION", long, value_parser, default_value = "paris")]
+    evm_version: EvmVersion,
+
+    /// The `solc` program to compile Solidity and Yul code with
+    #[clap(value_name = "PATH", long, value_parser, default_value = SOLC)]
+    solc: PathBuf,
+
+    /// The number of times the `solc` optimizer expects each opcode to be run
+    #[clap(value_name = "N", long, value_parser, default_value = "200")]
+    optimizer_runs: u32,
+
+    /// Compile Solidity code through the Yul IR
+    #[clap(long, value_parser)]
+    via_ir: bool,
+
+    /// Remap Solidity imports that start with PREFIX to PATH
+    #[clap(value_name = "PREFIX=PATH", long = "remapping", value_parser)]
+    remappings: Vec<String>,
+
+    /// The root of the Solidity source tree
+    #[clap(value_name = "DIR", long, value_parser)]
+    base_path: Option<PathBuf>,
+
+    /// Another directory to look for Solidity imports in, such as `node_modules`
+    #[clap(value_name = "DIR", long = "include-path", value_parser)]
+    include_paths: Vec<PathBuf>,
+
+    /// The contract to compile, if the input defines several
+    #[clap(value_name = "NAME", long, value_parser)]
+    contract: Option<String>,
+
     /// Disable precise EVM gas accounting
     #[clap(long = "fno-gas-accounting", value_parser)]
     no_gas_accounting: bool,
@@ -53,9 +79,27 @@ struct Options {
     #[clap(long = "fno-program-counter", value_parser)]
     no_program_counter: bool,
 
-    /// The input file
+    /// The number of decimal places dropped when converting yoctoNEAR to wei
+    #[clap(
+        value_name = "N",
+        long,
+        value_parser = clap::value_parser!(u8).range(0..=24),
+        default_value = "0"
+    )]
+    value_scale: u8,
+
+    /// Make the contract upgradable by the given NEAR account
+    #[clap(value_name = "ACCOUNT_ID", long, value_parser)]
+    upgrade_owner: Option<String>,
+
+    /// Refuse to compile unless the contract can upgrade the given one,
+    /// given as Solidity code or as a `solc --storage-layout` JSON file
+    #[clap(value_name = "FILE", long, value_parser)]
+    upgrade_from: Option<PathBuf>,
+
+    /// The input files, or directories to compile every contract in
     #[clap(value_name = "FILE", value_parser, default_value = "/dev/stdin")]
-    input: PathBuf,
+    input: Vec<PathBuf>,
 
     /// The output file
     #[clap(
@@ -66,14 +110,31 @@ struct Options {
     )]
     output: PathBuf,
 
+    /// The manifest listing the contracts to compile, by default `evm2near.toml`
+    /// in the working directory if there is one
+    #[clap(value_name = "FILE", long, value_parser)]
+    manifest: Option<PathBuf>,
+
+    /// The directory to write an output file for each input file to, named after it
+    #[clap(value_name = "DIR", long, value_parser)]
+    out_dir: Option<PathBuf>,
+
+    /// The number of contracts to compile at once, by default one per CPU
+    #[clap(value_name = "N", short = 'j', long, value_parser)]
+    jobs: Option<usize>,
+
     /// The output format
     #[clap(short = 't', long, value_parser, default_value = "auto")]
     to: OutputFormat,
 
     /// The output ABI
-    #[clap(short = 'b', long, value_parser, default_value = "near")]
+    #[clap(short = 'b', long = "output-abi", value_parser, default_value = "near")]
     abi: OutputABI,
 
+    /// The JSON ABI of the contract, used to generate a method for each function
+    #[clap(value_name = "FILE", long = "abi", value_parser)]
+    abi_file: Option<PathBuf>,
+
     /// Enable verbose output
     #[clap(short = 'v', long, value_parser)]
     verbose: bool,
@@ -91,118 +152,484 @@ macro_rules! abort {
 }
 
 fn main() -> impl std::process::Termination {
-    let options = Options::parse_from(wild::args());
-
-    let input_path = options.input.as_path();
-    let input_ext = input_path.extension().and_then(OsStr::to_str);
-    let input_format = match options.from {
-        InputFormat::Auto => match parse_input_extension(input_ext) {
-            Some(format) => format,
-            None => InputFormat::Bin, // the default
-        },
-        format => format,
+    let matches = Options::command().get_matches_from(wild::args());
+    let options = match Options::from_arg_matches(&matches) {
+        Ok(options) => options,
+        Err(err) => err.exit(),
     };
+    // Only the options given on the command line override the manifest
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
 
-    let mut input = match options.input.to_str() {
-        Some("/dev/stdin") | Some("-") => Box::new(stdin()) as Box<dyn Read>,
-        _ => match File::open(&options.input) {
-            Ok(file) => Box::new(file) as Box<dyn Read>,
-            Err(err) => abort!(
-                "Could not open input file `{}': {}",
-                options.input.display(),
-                err
-            ),
+    let manifest_path = match &options.manifest {
+        Some(path) => Some(path.as_path()),
+        None => Some(Path::new(MANIFEST)).filter(|path| path.exists()),
+    };
+    let manifest = match manifest_path {
+        None => Manifest::default(),
+        Some(path) => match Manifest::load(path) {
+            Ok(manifest) => manifest,
+            Err(err) => abort!("Could not load manifest `{}': {}", path.display(), err),
         },
     };
 
-    let mut input_buffer = String::new();
-    match input.read_to_string(&mut input_buffer) {
-        Ok(_) => {}
-        Err(err) => abort!(
-            "Could not read input file `{}': {}",
-            options.input.display(),
-            err
-        ),
+    let solc_settings = SolcSettings {
+        path: given("solc").then(|| options.solc.clone()),
+        evm_version: given("evm-version").then_some(options.evm_version),
+        optimizer_runs: given("optimizer-runs").then_some(options.optimizer_runs),
+        via_ir: given("via-ir").then_some(true),
+        remappings: given("remappings").then(|| options.remappings.clone()),
+        base_path: options.base_path.clone(),
+        include_paths: given("include-paths").then(|| options.include_paths.clone()),
+    }
+    .or(&manifest.solc);
+    let input_config = InputConfig {
+        format: options.from.clone(),
+        solc: solc_settings.solc(),
+        evm_version: solc_settings.evm_version(),
+        contract: None,
+    };
+
+    let settings = Settings {
+        output_abi: given("abi").then(|| options.abi.clone()),
+        chain_id: given("chain-id").then(|| match parse_chain_id(&options.chain_id) {
+            Ok(chain_id) => chain_id,
+            Err(err) => abort!("Could not parse `{}': {}", options.chain_id, err),
+        }),
+        gas_accounting: given("no-gas-accounting").then_some(false),
+        program_counter: given("no-program-counter").then_some(false),
+        value_scale: given("value-scale").then_some(options.value_scale),
+        upgrade_owner: options.upgrade_owner.clone(),
+        debug_dir: options.debug_folder.clone(),
     };
 
-    let input_program = match input_format {
-        InputFormat::Auto | InputFormat::Bin => {
-            match decode_bytecode(&input_buffer) {
-                Err(err) => abort!("{}", err), // TODO
-                Ok(program) => program,
+    if given("input") || manifest.contracts.is_empty() {
+        let settings = settings.or(&manifest.compiler);
+        match &options.out_dir {
+            None => compile_file(&options, &input_config, settings),
+            Some(out_dir) => {
+                let jobs = input_jobs(&options, &settings, out_dir);
+                compile_batch(&options, &input_config, jobs);
             }
         }
-        InputFormat::Sol => match solidity::compile(input_path) {
-            Ok(program) => program,
-            Err(err) => abort!(
-                "Failed to compile {} code: {}",
-                "Solidity",
-                err.with_program(SOLC)
-            ),
-        },
+    } else {
+        let jobs = match manifest_jobs(&options, &manifest, &settings) {
+            Ok(jobs) => jobs,
+            Err(message) => abort!("{}", message),
+        };
+        compile_batch(&options, &input_config, jobs);
+    }
+}
+
+/// A contract to compile, and how to compile it.
+struct Job {
+    input_path: PathBuf,
+    output_path: PathBuf,
+    /// The contract to compile, if the input defines several
+    contract: Option<String>,
+    abi_file: Option<PathBuf>,
+    upgrade_from: Option<PathBuf>,
+    settings: Settings,
+}
+
+/// A contract read from an input file, ready to compile.
+struct Input {
+    path: PathBuf,
+    output_path: PathBuf,
+    program: Program,
+    metadata: Option<Metadata>,
+    abi: Option<Functions>,
+    output_abi: OutputABI,
+    config: CompilerConfig,
+}
+
+/// Compiles a single input file to the output file.
+fn compile_file(options: &Options, input_config: &InputConfig, settings: Settings) {
+    let input_path = match options.input.as_slice() {
+        [input_path] => input_path,
+        _ => abort!("Compiling several inputs requires `--out-dir'"),
+    };
+    let job = Job {
+        input_path: input_path.clone(),
+        output_path: options.output.clone(),
+        contract: options.contract.clone(),
+        abi_file: options.abi_file.clone(),
+        upgrade_from: options.upgrade_from.clone(),
+        settings,
     };
+    let input = prepare_input(options, input_config, job);
 
-    let input_abi = match input_format {
-        InputFormat::Auto | InputFormat::Bin => None,
-        InputFormat::Sol => match solidity::compile_abi(input_path) {
-            Ok(abi) => Some(abi),
-            Err(err) => abort!(
-                "Failed to compile {} ABI: {}",
-                "Solidity",
-                err.with_program(SOLC)
-            ),
-        },
+    let module = evm2near::compile_program(
+        &input.program,
+        input.metadata.as_ref(),
+        input.abi,
+        &input.config,
+    );
+    let module = match module {
+        Ok(module) => module,
+        Err(err) => abort!("Failed to compile `{}': {}", input.path.display(), err),
     };
 
-    let mut output = match options.output.to_str() {
+    let mut output = match input.output_path.to_str() {
         Some("/dev/stdout") | Some("-") => Box::new(stdout()) as Box<dyn Write>,
         _ => match OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(&options.output)
+            .open(&input.output_path)
         {
             Ok(file) => Box::new(file) as Box<dyn Write>,
             Err(err) => abort!(
                 "Could not open output file `{}': {}",
-                options.output.display(),
+                input.output_path.display(),
                 err
             ),
         },
     };
 
-    let runtime_wasm = include_bytes!("../../../evmlib.wasm");
-    let runtime_wasi = include_bytes!("../../../evmlib.wasi");
-    let current_runtime = match options.abi {
-        OutputABI::Near => runtime_wasm.to_vec(),
-        OutputABI::Wasi => runtime_wasi.to_vec(),
+    if let Err(err) = output.write_all(&module) {
+        abort!(
+            "Could not write output file `{}': {}",
+            input.output_path.display(),
+            err
+        );
+    }
+}
+
+/// The jobs for the input files, and the contracts in the input directories,
+/// each written to the output directory under the name of its input file.
+fn input_jobs(options: &Options, settings: &Settings, out_dir: &Path) -> Vec<Job> {
+    let mut input_paths = Vec::new();
+    for input_path in &options.input {
+        match input::find_inputs(input_path, &options.from) {
+            Ok(paths) => input_paths.extend(paths),
+            Err(err) => abort!("{}", err),
+        }
+    }
+    input_paths
+        .into_iter()
+        .map(|input_path| {
+            let name = contract_name(&input_path);
+            let mut settings = settings.clone();
+            settings.debug_dir = settings.debug_dir.map(|dir| dir.join(&name));
+            Job {
+                output_path: out_dir.join(format!("{}.wasm", name)),
+                input_path,
+                contract: options.contract.clone(),
+                abi_file: options.abi_file.clone(),
+                upgrade_from: options.upgrade_from.clone(),
+                settings,
+            }
+        })
+        .collect()
+}
+
+/// The jobs for the contracts in the manifest, with the settings given on
+/// the command line overriding those in the manifest. The contract, ABI and
+/// old contract given on the command line can only override those of a
+/// manifest with a single contract.
+fn manifest_jobs(
+    options: &Options,
+    manifest: &Manifest,
+    settings: &Settings,
+) -> Result<Vec<Job>, String> {
+    let per_contract =
+        options.contract.is_some() || options.abi_file.is_some() || options.upgrade_from.is_some();
+    if per_contract && manifest.contracts.len() > 1 {
+        return Err(
+            "Cannot use `--contract', `--abi' or `--upgrade-from' with a manifest of several contracts"
+                .to_string(),
+        );
+    }
+    let out_dir = options.out_dir.as_ref().or(manifest.out_dir.as_ref());
+    manifest
+        .contracts
+        .iter()
+        .map(|contract| {
+            let name = match &contract.name {
+                Some(name) => name.clone(),
+                None => contract_name(&contract.input),
+            };
+            let output_path = match (&contract.output, out_dir) {
+                (Some(output_path), _) => output_path.clone(),
+                (None, Some(out_dir)) => out_dir.join(format!("{}.wasm", name)),
+                (None, None) => {
+                    return Err(format!(
+                    "No output file for `{}': set its `output' or the `out-dir' in the manifest",
+                    contract.input.display()
+                ))
+                }
+            };
+            let mut settings = settings
+                .clone()
+                .or(&contract.compiler)
+                .or(&manifest.compiler);
+            settings.debug_dir = settings.debug_dir.map(|dir| dir.join(&name));
+            Ok(Job {
+                input_path: contract.input.clone(),
+                output_path,
+                contract: options.contract.clone().or_else(|| contract.name.clone()),
+                abi_file: options.abi_file.clone().or_else(|| contract.abi.clone()),
+                upgrade_from: options
+                    .upgrade_from
+                    .clone()
+                    .or_else(|| contract.upgrade_from.clone()),
+                settings,
+            })
+        })
+        .collect()
+}
+
+/// Compiles each contract to its own output file. The inputs are all read
+/// and checked before any is compiled, and the runtime library is parsed
+/// only once for all of them.
+fn compile_batch(options: &Options, input_config: &InputConfig, jobs: Vec<Job>) {
+    if options.output != Path::new("/dev/stdout") {
+        abort!("Cannot use `-o' when compiling into an output directory");
+    }
+    if jobs.len() > 1 && (options.abi_file.is_some() || options.upgrade_from.is_some()) {
+        abort!("Cannot use `--abi' or `--upgrade-from' when compiling several contracts");
+    }
+
+    let threads = match options.jobs {
+        Some(jobs) => jobs.max(1),
+        None => thread::available_parallelism().map_or(1, usize::from),
     };
+    // Compiling the sources runs `solc` or `vyper`, so it is done in parallel too
+    let inputs = parallel_map(threads, jobs, |job| {
+        prepare_input(options, input_config, job)
+    });
+    let mut output_paths = HashSet::new();
+    for input in &inputs {
+        if !output_paths.insert(&input.output_path) {
+            abort!(
+                "Several contracts would be written to `{}'",
+                input.output_path.display()
+            );
+        }
+        let out_dir = input.output_path.parent().unwrap_or_else(|| Path::new(""));
+        if let Err(err) = fs::create_dir_all(out_dir) {
+            abort!(
+                "Could not create output directory `{}': {}",
+                out_dir.display(),
+                err
+            );
+        }
+    }
 
-    let runtime_library = wasm_translate::parse(&current_runtime).unwrap();
-
-    let module = compile(
-        &input_program,
-        input_abi,
-        runtime_library,
-        CompilerConfig::new(
-            options.debug_folder,
-            0, // TODO: -O{0,1,2}
-            !options.no_gas_accounting,
-            !options.no_program_counter,
-            match options.chain_id.as_str() {
-                "mainnet" => 1313161554,
-                "testnet" => 1313161555,
-                "betanet" => 1313161556,
-                s => match s.parse::<u64>() {
-                    Ok(n) => n,
-                    Err(err) => abort!("Could not parse `{}': {}", s, err),
-                },
+    let mut runtimes = HashMap::new();
+    for input in &inputs {
+        if !runtimes.contains_key(&input.output_abi) {
+            match Runtime::new(&input.output_abi) {
+                Ok(runtime) => runtimes.insert(input.output_abi.clone(), runtime),
+                Err(err) => abort!("{}", err),
+            };
+        }
+    }
+
+    let failed = AtomicBool::new(false);
+    parallel_map(threads, inputs, |input| {
+        let runtime = &runtimes[&input.output_abi];
+        let module = runtime.compile(
+            &input.program,
+            input.metadata.as_ref(),
+            input.abi,
+            &input.config,
+        );
+        let result = match module {
+            Err(err) => Err(format!(
+                "Failed to compile `{}': {}",
+                input.path.display(),
+                err
+            )),
+            Ok(module) => fs::write(&input.output_path, module).map_err(|err| {
+                format!(
+                    "Could not write output file `{}': {}",
+                    input.output_path.display(),
+                    err
+                )
+            }),
+        };
+        match result {
+            Ok(()) if options.verbose => eprintln!(
+                "Compiled `{}' to `{}'",
+                input.path.display(),
+                input.output_path.display()
+            ),
+            Ok(()) => {}
+            Err(message) => {
+                eprintln!("{}", message);
+                failed.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+    if failed.load(Ordering::Relaxed) {
+        std::process::exit(1);
+    }
+}
+
+/// Applies `f` to each item on up to `jobs` threads, returning the results
+/// in the order of the items.
+fn parallel_map<T: Send, U: Send>(jobs: usize, items: Vec<T>, f: impl Fn(T) -> U + Sync) -> Vec<U> {
+    let mut results: Vec<Option<U>> = items.iter().map(|_| None).collect();
+    let queue = Mutex::new(items.into_iter().zip(results.iter_mut()));
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let (item, result) = match queue.lock().unwrap().next() {
+                    Some(next) => next,
+                    None => break,
+                };
+                *result = Some(f(item));
+            });
+        }
+    });
+    drop(queue);
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+/// Reads the input file and compiles it to EVM bytecode if need be, then
+/// checks the contract against its ABI and the contract it upgrades.
+fn prepare_input(options: &Options, input_config: &InputConfig, job: Job) -> Input {
+    let input_config = InputConfig {
+        contract: job.contract.clone(),
+        ..input_config.clone()
+    };
+    let contract = match read_contract(options, &input_config, &job) {
+        Ok(contract) => contract,
+        Err(err) => abort!("Failed to compile `{}': {}", job.input_path.display(), err),
+    };
+
+    let mut config = job.settings.config();
+    if let Some(debug_dir) = &job.settings.debug_dir {
+        config = config.debug_path(debug_dir);
+    }
+    Input {
+        path: job.input_path,
+        output_path: job.output_path,
+        program: contract.program,
+        metadata: contract.metadata,
+        abi: contract.abi,
+        output_abi: job.settings.output_abi.unwrap_or(OutputABI::Near),
+        config,
+    }
+}
+
+/// Reads the contract from the input file, or from stdin, with the ABI
+/// given on its own, and checks it.
+fn read_contract(
+    options: &Options,
+    input_config: &InputConfig,
+    job: &Job,
+) -> Result<input::Input, Error> {
+    let input_path = job.input_path.as_path();
+    let mut contract = match input_path.to_str() {
+        Some("/dev/stdin") | Some("-") => {
+            let mut input_buffer = Vec::new();
+            stdin()
+                .read_to_end(&mut input_buffer)
+                .map_err(|err| Error::Read(input_path.to_path_buf(), err))?;
+            input::parse_input(input_path, &input_buffer, input_config)?
+        }
+        _ => input::read_input(input_path, input_config)?,
+    };
+
+    if options.verbose {
+        if let Some(name) = &contract.name {
+            eprintln!("Compiling contract `{}'", name);
+        }
+        match &contract.metadata {
+            Some(metadata) => eprintln!("Contract metadata: {}", metadata),
+            None => eprintln!("Contract metadata: none"),
+        }
+    }
+
+    if let Some(abi_path) = &job.abi_file {
+        contract.abi = Some(input::read_abi(abi_path)?);
+    }
+    contract.check_abi()?;
+    if let Some(old_path) = &job.upgrade_from {
+        contract.check_upgrade(old_path, input_config)?;
+    }
+    Ok(contract)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evm2near::manifest::ContractEntry;
+
+    fn entry(input: &str) -> ContractEntry {
+        ContractEntry {
+            input: PathBuf::from(input),
+            output: None,
+            name: None,
+            abi: None,
+            upgrade_from: None,
+            compiler: Settings::default(),
+        }
+    }
+
+    #[test]
+    fn test_manifest_jobs() {
+        let options = Options::parse_from(["evm2near", "--contract", "Token", "--abi", "cli.json"]);
+        let manifest = Manifest {
+            out_dir: Some(PathBuf::from("build")),
+            compiler: Settings {
+                chain_id: Some(1),
+                value_scale: Some(6),
+                ..Settings::default()
             },
-        ),
-    );
+            contracts: vec![ContractEntry {
+                name: Some("Coin".to_string()),
+                abi: Some(PathBuf::from("coin.json")),
+                upgrade_from: Some(PathBuf::from("old.sol")),
+                compiler: Settings {
+                    value_scale: Some(12),
+                    ..Settings::default()
+                },
+                ..entry("src/Coin.sol")
+            }],
+            ..Manifest::default()
+        };
+        let settings = Settings {
+            chain_id: Some(2),
+            ..Settings::default()
+        };
+        let jobs = manifest_jobs(&options, &manifest, &settings).unwrap();
+        assert_eq!(jobs.len(), 1);
+        let job = &jobs[0];
+        assert_eq!(job.input_path, Path::new("src/Coin.sol"));
+        assert_eq!(job.output_path, Path::new("build/Coin.wasm"));
+        assert_eq!(job.contract.as_deref(), Some("Token"));
+        assert_eq!(job.abi_file.as_deref(), Some(Path::new("cli.json")));
+        assert_eq!(job.upgrade_from.as_deref(), Some(Path::new("old.sol")));
+        assert_eq!(job.settings.chain_id, Some(2));
+        assert_eq!(job.settings.value_scale, Some(12));
+    }
 
-    let module_bytes = module.finish();
-    output
-        .write_all(&module_bytes)
-        .expect("Failed to write module");
+    #[test]
+    fn test_manifest_jobs_several() {
+        let manifest = Manifest {
+            out_dir: Some(PathBuf::from("build")),
+            contracts: vec![entry("src/Token.sol"), entry("src/Counter.sol")],
+            ..Manifest::default()
+        };
+        let options = Options::parse_from(["evm2near"]);
+        let jobs = manifest_jobs(&options, &manifest, &Settings::default()).unwrap();
+        let outputs: Vec<_> = jobs.iter().map(|job| job.output_path.as_path()).collect();
+        assert_eq!(
+            outputs,
+            vec![
+                Path::new("build/Token.wasm"),
+                Path::new("build/Counter.wasm")
+            ]
+        );
+        for args in [
+            ["evm2near", "--contract", "Token"],
+            ["evm2near", "--abi", "token.json"],
+        ] {
+            let options = Options::parse_from(args);
+            assert!(manifest_jobs(&options, &manifest, &Settings::default()).is_err());
+        }
+    }
 }
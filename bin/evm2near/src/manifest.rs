@@ -0,0 +1,369 @@
+// This is free and unencumbered software released into the public domain.
+
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{parse_chain_id, CompilerConfig, MAX_VALUE_SCALE},
+    format::OutputABI,
+    solidity::{EvmVersion, Solc, SOLC},
+};
+
+/// The name of the manifest file, which is looked for in the working directory.
+pub const MANIFEST: &str = "evm2near.toml";
+
+/// A project manifest, which lists the contracts to compile and how to
+/// compile them. Relative paths are relative to the manifest file.
+///
+/// ```toml
+/// out-dir = "build"
+///
+/// [compiler]
+/// chain-id = "testnet"
+///
+/// [solc]
+/// evm-version = "shanghai"
+/// remappings = ["@openzeppelin/=lib/openzeppelin-contracts/"]
+///
+/// [[contract]]
+/// input = "src/Token.sol"
+/// name = "Token"
+///
+/// [[contract]]
+/// input = "src/Counter.sol"
+/// output = "counter.wasm"
+/// compiler = { value-scale = 6, upgrade-owner = "alice.near" }
+/// ```
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    /// The directory to write the contracts that have no `output` to
+    pub out_dir: Option<PathBuf>,
+    /// The settings of every contract, unless it has its own
+    #[serde(default)]
+    pub compiler: Settings,
+    #[serde(default)]
+    pub solc: SolcSettings,
+    #[serde(default, rename = "contract")]
+    pub contracts: Vec<ContractEntry>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ContractEntry {
+    pub input: PathBuf,
+    /// The output file, by default named after the contract (or else the input
+    /// file) in the output directory
+    pub output: Option<PathBuf>,
+    /// The contract to compile, if the input defines several
+    pub name: Option<String>,
+    /// The JSON ABI of the contract, used to generate a method for each function
+    pub abi: Option<PathBuf>,
+    /// The contract, or storage layout, that this contract must be able to upgrade
+    pub upgrade_from: Option<PathBuf>,
+    #[serde(default)]
+    pub compiler: Settings,
+}
+
+/// The settings of a `CompilerConfig`, any of which may be left unset to
+/// take it from elsewhere.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    pub output_abi: Option<OutputABI>,
+    #[serde(default, deserialize_with = "deserialize_chain_id")]
+    pub chain_id: Option<u64>,
+    pub gas_accounting: Option<bool>,
+    pub program_counter: Option<bool>,
+    pub value_scale: Option<u8>,
+    pub upgrade_owner: Option<String>,
+    pub debug_dir: Option<PathBuf>,
+}
+
+/// The settings of `solc`, and the EVM version to compile source code for.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SolcSettings {
+    pub path: Option<PathBuf>,
+    pub evm_version: Option<EvmVersion>,
+    pub optimizer_runs: Option<u32>,
+    pub via_ir: Option<bool>,
+    pub remappings: Option<Vec<String>>,
+    pub base_path: Option<PathBuf>,
+    pub include_paths: Option<Vec<PathBuf>>,
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Read(io::Error),
+    Toml(toml::de::Error),
+    ValueScale(u8),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ManifestError::*;
+        match self {
+            Read(err) => write!(f, "{}", err),
+            Toml(err) => write!(f, "{}", err),
+            ValueScale(scale) => write!(
+                f,
+                "`value-scale' is {}, but at most {}",
+                scale, MAX_VALUE_SCALE
+            ),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest, ManifestError> {
+        let text = fs::read_to_string(path).map_err(ManifestError::Read)?;
+        let mut manifest = parse_str(&text)?;
+        manifest.resolve_paths(path.parent().unwrap_or_else(|| Path::new("")));
+        Ok(manifest)
+    }
+
+    /// Makes the relative paths in the manifest relative to its directory,
+    /// including the target paths of import remappings.
+    fn resolve_paths(&mut self, dir: &Path) {
+        let resolve = |path: &mut PathBuf| *path = dir.join(&*path);
+        let resolve_remapping = |remapping: &mut String| {
+            if let Some((prefix, target)) = remapping.split_once('=') {
+                let target = dir.join(target);
+                *remapping = format!("{}={}", prefix, target.display());
+            }
+        };
+        self.out_dir.iter_mut().for_each(resolve);
+        self.compiler.debug_dir.iter_mut().for_each(resolve);
+        // A bare program name is looked up in PATH
+        self.solc
+            .path
+            .iter_mut()
+            .filter(|path| path.components().count() > 1)
+            .for_each(resolve);
+        self.solc.base_path.iter_mut().for_each(resolve);
+        self.solc
+            .include_paths
+            .iter_mut()
+            .flatten()
+            .for_each(resolve);
+        self.solc
+            .remappings
+            .iter_mut()
+            .flatten()
+            .for_each(resolve_remapping);
+        for contract in &mut self.contracts {
+            resolve(&mut contract.input);
+            contract.output.iter_mut().for_each(resolve);
+            contract.abi.iter_mut().for_each(resolve);
+            contract.upgrade_from.iter_mut().for_each(resolve);
+            contract.compiler.debug_dir.iter_mut().for_each(resolve);
+        }
+    }
+}
+
+pub fn parse_str(text: &str) -> Result<Manifest, ManifestError> {
+    let manifest: Manifest = toml::from_str(text).map_err(ManifestError::Toml)?;
+    let contract_settings = manifest.contracts.iter().map(|c| &c.compiler);
+    for settings in std::iter::once(&manifest.compiler).chain(contract_settings) {
+        match settings.value_scale {
+            Some(scale) if scale > MAX_VALUE_SCALE => return Err(ManifestError::ValueScale(scale)),
+            _ => {}
+        }
+    }
+    Ok(manifest)
+}
+
+/// Accepts a chain ID as a number, or as the name of a network.
+fn deserialize_chain_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ChainId {
+        Number(u64),
+        Name(String),
+    }
+    match ChainId::deserialize(deserializer)? {
+        ChainId::Number(chain_id) => Ok(Some(chain_id)),
+        ChainId::Name(name) => match parse_chain_id(&name) {
+            Ok(chain_id) => Ok(Some(chain_id)),
+            Err(_) => Err(D::Error::custom(format!("unknown chain `{}'", name))),
+        },
+    }
+}
+
+impl Settings {
+    /// Takes each setting that is unset here from the other settings.
+    pub fn or(self, other: &Settings) -> Settings {
+        Settings {
+            output_abi: self.output_abi.or_else(|| other.output_abi.clone()),
+            chain_id: self.chain_id.or(other.chain_id),
+            gas_accounting: self.gas_accounting.or(other.gas_accounting),
+            program_counter: self.program_counter.or(other.program_counter),
+            value_scale: self.value_scale.or(other.value_scale),
+            upgrade_owner: self.upgrade_owner.or_else(|| other.upgrade_owner.clone()),
+            debug_dir: self.debug_dir.or_else(|| other.debug_dir.clone()),
+        }
+    }
+
+    /// The compiler configuration, with the defaults for the unset settings.
+    /// The debug directory is left for the caller to set, since it must
+    /// differ between the contracts that share these settings.
+    pub fn config(&self) -> CompilerConfig {
+        let mut config = CompilerConfig::new();
+        if let Some(output_abi) = &self.output_abi {
+            config = config.output_abi(output_abi.clone());
+        }
+        if let Some(chain_id) = self.chain_id {
+            config = config.chain_id(chain_id);
+        }
+        if let Some(gas_accounting) = self.gas_accounting {
+            config = config.gas_accounting(gas_accounting);
+        }
+        if let Some(program_counter) = self.program_counter {
+            config = config.program_counter(program_counter);
+        }
+        if let Some(value_scale) = self.value_scale {
+            config = config.value_scale(value_scale);
+        }
+        if let Some(upgrade_owner) = &self.upgrade_owner {
+            config = config.upgrade_owner(upgrade_owner);
+        }
+        config
+    }
+}
+
+impl SolcSettings {
+    /// Takes each setting that is unset here from the other settings.
+    pub fn or(self, other: &SolcSettings) -> SolcSettings {
+        SolcSettings {
+            path: self.path.or_else(|| other.path.clone()),
+            evm_version: self.evm_version.or(other.evm_version),
+            optimizer_runs: self.optimizer_runs.or(other.optimizer_runs),
+            via_ir: self.via_ir.or(other.via_ir),
+            remappings: self.remappings.or_else(|| other.remappings.clone()),
+            base_path: self.base_path.or_else(|| other.base_path.clone()),
+            include_paths: self.include_paths.or_else(|| other.include_paths.clone()),
+        }
+    }
+
+    /// The `solc` to compile with, with the defaults for the unset settings.
+    pub fn solc(&self) -> Solc {
+        Solc {
+            path: self.path.clone().unwrap_or_else(|| PathBuf::from(SOLC)),
+            optimizer_runs: self.optimizer_runs.unwrap_or(200),
+            via_ir: self.via_ir.unwrap_or(false),
+            remappings: self.remappings.clone().unwrap_or_default(),
+            base_path: self.base_path.clone(),
+            include_paths: self.include_paths.clone().unwrap_or_default(),
+        }
+    }
+
+    /// The EVM version, which is Paris by default.
+    pub fn evm_version(&self) -> EvmVersion {
+        self.evm_version.unwrap_or(EvmVersion::Paris)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MANIFEST_TOML: &str = r#"
+out-dir = "build"
+
+[compiler]
+chain-id = "testnet"
+output-abi = "wasi"
+
+[solc]
+evm-version = "shanghai"
+remappings = ["@openzeppelin/=lib/openzeppelin-contracts/"]
+
+[[contract]]
+input = "src/Token.sol"
+name = "Token"
+
+[[contract]]
+input = "src/Counter.sol"
+output = "counter.wasm"
+
+[contract.compiler]
+chain-id = 1313161556
+value-scale = 6
+"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_str(MANIFEST_TOML).unwrap();
+        assert_eq!(manifest.out_dir, Some(PathBuf::from("build")));
+        assert_eq!(manifest.compiler.chain_id, Some(1313161555));
+        assert_eq!(manifest.compiler.output_abi, Some(OutputABI::Wasi));
+        assert_eq!(manifest.solc.evm_version, Some(EvmVersion::Shanghai));
+        assert_eq!(manifest.contracts.len(), 2);
+        assert_eq!(manifest.contracts[0].name.as_deref(), Some("Token"));
+        assert_eq!(manifest.contracts[0].compiler, Settings::default());
+        assert_eq!(
+            manifest.contracts[1].output,
+            Some(PathBuf::from("counter.wasm"))
+        );
+        assert_eq!(manifest.contracts[1].compiler.chain_id, Some(1313161556));
+
+        assert_eq!(parse_str("").unwrap(), Manifest::default());
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        assert!(matches!(
+            parse_str("[compiler]\nchain-id = \"devnet\"\n"),
+            Err(ManifestError::Toml(_))
+        ));
+        assert!(matches!(
+            parse_str("[compiler]\nvalue-scle = 6\n"),
+            Err(ManifestError::Toml(_))
+        ));
+        assert!(matches!(
+            parse_str("[[contract]]\ninput = \"a.sol\"\n[contract.compiler]\nvalue-scale = 25\n"),
+            Err(ManifestError::ValueScale(25))
+        ));
+    }
+
+    #[test]
+    fn test_settings_or() {
+        let manifest = parse_str(MANIFEST_TOML).unwrap();
+        let command_line = Settings {
+            chain_id: Some(1),
+            ..Settings::default()
+        };
+        let settings = command_line
+            .or(&manifest.contracts[1].compiler)
+            .or(&manifest.compiler);
+        assert_eq!(settings.chain_id, Some(1));
+        assert_eq!(settings.value_scale, Some(6));
+        assert_eq!(settings.output_abi, Some(OutputABI::Wasi));
+        assert_eq!(settings.gas_accounting, None);
+    }
+
+    #[test]
+    fn test_resolve_paths() {
+        let mut manifest = parse_str(MANIFEST_TOML).unwrap();
+        manifest.solc.path = Some(PathBuf::from("solc-0.8.20"));
+        manifest.resolve_paths(Path::new("project"));
+        assert_eq!(manifest.out_dir, Some(PathBuf::from("project/build")));
+        assert_eq!(manifest.solc.path, Some(PathBuf::from("solc-0.8.20")));
+        assert_eq!(
+            manifest.contracts[0].input,
+            PathBuf::from("project/src/Token.sol")
+        );
+        assert_eq!(
+            manifest.solc.remappings,
+            Some(vec![
+                "@openzeppelin/=project/lib/openzeppelin-contracts/".to_string()
+            ])
+        );
+    }
+}
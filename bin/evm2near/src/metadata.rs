@@ -0,0 +1,226 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::fmt;
+
+/// The metadata that `solc` appends to the runtime bytecode, as a
+/// CBOR map followed by its length as a 2-byte big-endian number.
+/// See: https://docs.soliditylang.org/en/develop/metadata.html#encoding-of-the-metadata-hash-in-the-bytecode
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Metadata {
+    /// The compiler version, like `0.8.16`
+    pub solc: Option<String>,
+    /// The multihash of the metadata file on IPFS
+    pub ipfs: Option<Vec<u8>>,
+    /// The Swarm hash of the metadata file, in either of its versions
+    pub bzzr: Option<Vec<u8>>,
+    pub experimental: bool,
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields = Vec::new();
+        if let Some(solc) = &self.solc {
+            fields.push(format!("solc {}", solc));
+        }
+        if let Some(ipfs) = &self.ipfs {
+            fields.push(format!("ipfs {}", base58(ipfs)));
+        }
+        if let Some(bzzr) = &self.bzzr {
+            fields.push(format!("bzzr 0x{}", hex::encode(bzzr)));
+        }
+        if self.experimental {
+            fields.push("experimental".to_string());
+        }
+        if fields.is_empty() {
+            write!(f, "empty")
+        } else {
+            write!(f, "{}", fields.join(", "))
+        }
+    }
+}
+
+/// Splits the metadata off the end of the bytecode, if there is any.
+/// The metadata is only recognized if its length suffix delimits a valid
+/// CBOR map, so that code which merely looks like metadata is left alone.
+pub fn split_metadata(bytecode: &[u8]) -> (&[u8], Option<Metadata>) {
+    if bytecode.len() < 2 {
+        return (bytecode, None);
+    }
+    let (rest, suffix) = bytecode.split_at(bytecode.len() - 2);
+    let len = usize::from(u16::from_be_bytes([suffix[0], suffix[1]]));
+    if len == 0 || len > rest.len() {
+        return (bytecode, None);
+    }
+    let (code, cbor) = rest.split_at(rest.len() - len);
+    match parse_cbor_map(cbor) {
+        Some(metadata) => (code, Some(metadata)),
+        None => (bytecode, None),
+    }
+}
+
+/// Parses the subset of CBOR that compilers use for metadata: a map from
+/// text keys to byte strings, text strings and booleans.
+fn parse_cbor_map(cbor: &[u8]) -> Option<Metadata> {
+    let mut reader = CborReader(cbor);
+    let entries = match reader.byte()? {
+        head @ 0xA0..=0xB7 => head - 0xA0,
+        _ => return None,
+    };
+    let mut metadata = Metadata::default();
+    for _ in 0..entries {
+        let key = match reader.value()? {
+            CborValue::Text(key) => key,
+            _ => return None,
+        };
+        let value = reader.value()?;
+        match (key, value) {
+            ("solc", CborValue::Bytes([major, minor, patch])) => {
+                metadata.solc = Some(format!("{}.{}.{}", major, minor, patch))
+            }
+            ("solc", CborValue::Text(version)) => metadata.solc = Some(version.to_string()),
+            ("ipfs", CborValue::Bytes(hash)) => metadata.ipfs = Some(hash.to_vec()),
+            ("bzzr0" | "bzzr1", CborValue::Bytes(hash)) => metadata.bzzr = Some(hash.to_vec()),
+            ("experimental", CborValue::Bool(experimental)) => metadata.experimental = experimental,
+            _ => {} // unknown entries are fine as long as they are well-formed
+        }
+    }
+    // The map must span exactly the length given in the suffix
+    if !reader.0.is_empty() {
+        return None;
+    }
+    Some(metadata)
+}
+
+enum CborValue<'a> {
+    Bytes(&'a [u8]),
+    Text(&'a str),
+    Bool(bool),
+}
+
+struct CborReader<'a>(&'a [u8]);
+
+impl<'a> CborReader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (byte, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(*byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.0.len() {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    /// Reads the length that follows the head byte of a string
+    fn len(&mut self, info: u8) -> Option<usize> {
+        match info {
+            0..=23 => Some(usize::from(info)),
+            24 => self.byte().map(usize::from),
+            25 => Some(usize::from(u16::from_be_bytes([
+                self.byte()?,
+                self.byte()?,
+            ]))),
+            _ => None,
+        }
+    }
+
+    fn value(&mut self) -> Option<CborValue<'a>> {
+        let head = self.byte()?;
+        match (head >> 5, head & 0x1F) {
+            (2, info) => {
+                let len = self.len(info)?;
+                self.bytes(len).map(CborValue::Bytes)
+            }
+            (3, info) => {
+                let len = self.len(info)?;
+                let text = std::str::from_utf8(self.bytes(len)?).ok()?;
+                Some(CborValue::Text(text))
+            }
+            (7, 20) => Some(CborValue::Bool(false)),
+            (7, 21) => Some(CborValue::Bool(true)),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes bytes in Base58, as used for IPFS hashes.
+fn base58(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    // The digits in base 58, least significant first
+    let mut digits: Vec<u8> = Vec::new();
+    for byte in &bytes[zeros..] {
+        let mut carry = u32::from(*byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut result = "1".repeat(zeros);
+    result.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|d| ALPHABET[usize::from(*d)] as char),
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The metadata appended by solc 0.8.16 by default, here with the SHA-256 of nothing
+    const IPFS_METADATA: &str = concat!(
+        "a2646970667358221220",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        "64736f6c63430008100033",
+    );
+
+    #[test]
+    fn test_split_metadata() {
+        let bytecode = hex::decode(["6080604052", IPFS_METADATA].concat()).unwrap();
+        let (code, metadata) = split_metadata(&bytecode);
+        assert_eq!(code, &[0x60, 0x80, 0x60, 0x40, 0x52]);
+        let metadata = metadata.unwrap();
+        assert_eq!(metadata.solc.as_deref(), Some("0.8.16"));
+        assert_eq!(metadata.ipfs.as_ref().unwrap().len(), 34);
+        assert_eq!(
+            metadata.to_string(),
+            "solc 0.8.16, ipfs QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n"
+        );
+
+        // With `--metadata-hash none`, only the compiler version remains
+        let bytecode = hex::decode("6080a164736f6c6343000810000a").unwrap();
+        let (code, metadata) = split_metadata(&bytecode);
+        assert_eq!(code, &[0x60, 0x80]);
+        assert_eq!(metadata.unwrap().solc.as_deref(), Some("0.8.16"));
+    }
+
+    #[test]
+    fn test_split_no_metadata() {
+        // The length suffix does not delimit a CBOR map
+        let bytecode = hex::decode("6080604052600a").unwrap();
+        assert_eq!(split_metadata(&bytecode), (&bytecode[..], None));
+        // The CBOR map is shorter than the length suffix says
+        let bytecode = hex::decode("a164736f6c634300081000ff000c").unwrap();
+        assert_eq!(split_metadata(&bytecode), (&bytecode[..], None));
+        assert_eq!(split_metadata(&[]), (&[][..], None));
+    }
+
+    #[test]
+    fn test_base58() {
+        assert_eq!(base58(b""), "");
+        assert_eq!(base58(&[0, 0, 1]), "112");
+        assert_eq!(base58(b"hello world"), "StV1DL6CwTryKyV");
+    }
+}
@@ -0,0 +1,176 @@
+// This is free and unencumbered software released into the public domain.
+
+//! EVM opcodes, including those introduced after the opcodes that `evm_rs`
+//! knows about, which are decoded here around its own decoder.
+
+use std::fmt;
+
+use evm_rs::DecodeError;
+
+/// An EVM opcode, either known to `evm_rs` or introduced since.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Evm(evm_rs::Opcode),
+    /// Shanghai (EIP-3855)
+    PUSH0,
+    /// Cancun (EIP-5656)
+    MCOPY,
+    /// Cancun (EIP-1153)
+    TLOAD,
+    /// Cancun (EIP-1153)
+    TSTORE,
+    /// Cancun (EIP-4844)
+    BLOBHASH,
+    /// Cancun (EIP-7516)
+    BLOBBASEFEE,
+}
+
+impl Opcode {
+    /// Returns the opcode of a byte that `evm_rs` does not know.
+    fn from_byte(byte: u8) -> Option<Self> {
+        use Opcode::*;
+        Some(match byte {
+            0x49 => BLOBHASH,
+            0x4a => BLOBBASEFEE,
+            0x5c => TLOAD,
+            0x5d => TSTORE,
+            0x5e => MCOPY,
+            0x5f => PUSH0,
+            _ => return None,
+        })
+    }
+
+    /// The size of the opcode in bytes, including any immediate operand.
+    pub fn size(&self) -> usize {
+        match self {
+            Opcode::Evm(op) => op.size(),
+            _ => 1,
+        }
+    }
+
+    /// Returns the opcode if it pushes an immediate operand, which PUSH0 does not
+    /// have, so that it is compiled like any other operator.
+    pub fn as_push(&self) -> Option<&evm_rs::Opcode> {
+        match self {
+            Opcode::Evm(op) if op.is_push() => Some(op),
+            _ => None,
+        }
+    }
+
+    pub fn is_push(&self) -> bool {
+        self.as_push().is_some()
+    }
+
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Opcode::Evm(op) if op.is_jump())
+    }
+
+    pub fn is_halt(&self) -> bool {
+        matches!(self, Opcode::Evm(op) if op.is_halt())
+    }
+
+    /// Returns the opcode without its immediate operand, if any.
+    pub fn zeroed(&self) -> Self {
+        match self {
+            Opcode::Evm(op) => Opcode::Evm(op.zeroed()),
+            op => op.clone(),
+        }
+    }
+}
+
+impl From<evm_rs::Opcode> for Opcode {
+    fn from(op: evm_rs::Opcode) -> Self {
+        Opcode::Evm(op)
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Opcode::Evm(op) => write!(f, "{}", op),
+            op => write!(f, "{:?}", op),
+        }
+    }
+}
+
+/// A decoded EVM program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program(pub Vec<Opcode>);
+
+/// Parses the name of an opcode, in uppercase.
+pub fn parse_opcode(name: &str) -> Option<Opcode> {
+    use Opcode::*;
+    Some(match name {
+        "PUSH0" => PUSH0,
+        "MCOPY" => MCOPY,
+        "TLOAD" => TLOAD,
+        "TSTORE" => TSTORE,
+        "BLOBHASH" => BLOBHASH,
+        "BLOBBASEFEE" => BLOBBASEFEE,
+        name => Evm(evm_rs::parse_opcode(name)?),
+    })
+}
+
+/// Decodes EVM bytecode, leaving each run of code without the opcodes that
+/// `evm_rs` does not know to its decoder.
+pub fn decode_program(bytecode: &[u8]) -> Result<Program, DecodeError> {
+    let mut program = Vec::new();
+    let mut run_start = 0;
+    let mut pos = 0;
+    while pos < bytecode.len() {
+        let byte = bytecode[pos];
+        match Opcode::from_byte(byte) {
+            Some(op) => {
+                let run = evm_rs::decode_program(&bytecode[run_start..pos])?;
+                program.extend(run.0.into_iter().map(Opcode::Evm));
+                program.push(op);
+                pos += 1;
+                run_start = pos;
+            }
+            None => {
+                // Skip the immediate operand of PUSH1 to PUSH32
+                pos += match byte {
+                    0x60..=0x7f => usize::from(byte - 0x5f) + 1,
+                    _ => 1,
+                };
+            }
+        }
+    }
+    let run = evm_rs::decode_program(&bytecode[run_start..])?;
+    program.extend(run.0.into_iter().map(Opcode::Evm));
+    Ok(Program(program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evm_rs::Opcode::*;
+
+    #[test]
+    fn test_decode_program() {
+        // PUSH0 PUSH1 0x5f TLOAD PUSH0 TSTORE STOP
+        let program = decode_program(&[0x5f, 0x60, 0x5f, 0x5c, 0x5f, 0x5d, 0x00]).unwrap();
+        assert_eq!(
+            program.0,
+            vec![
+                Opcode::PUSH0,
+                PUSH1(0x5f).into(),
+                Opcode::TLOAD,
+                Opcode::PUSH0,
+                Opcode::TSTORE,
+                STOP.into(),
+            ]
+        );
+        let sizes: Vec<_> = program.0.iter().map(Opcode::size).collect();
+        assert_eq!(sizes, vec![1, 2, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_parse_opcode() {
+        assert_eq!(parse_opcode("PUSH0"), Some(Opcode::PUSH0));
+        assert_eq!(parse_opcode("BLOBBASEFEE"), Some(Opcode::BLOBBASEFEE));
+        assert_eq!(parse_opcode("STOP"), Some(STOP.into()));
+        assert_eq!(parse_opcode("_EVM_INIT"), None);
+    }
+}
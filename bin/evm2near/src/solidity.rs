@@ -1,101 +1,432 @@
 // This is free and unencumbered software released into the public domain.
 
-use evm_rs::Program;
+use serde::Deserialize;
 use std::{
-    ffi::OsStr,
-    path::Path,
-    process::{Command, Output, Stdio},
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
 };
 
-use crate::{abi::Functions, decode::decode_bytecode, error::CompileError};
+use crate::{
+    abi::Functions,
+    decode::decode_bytecode,
+    error::FrontendError,
+    frontend::{execute, Contract, Frontend},
+    storage_layout::StorageLayout,
+};
 
 pub const SOLC: &str = "solc";
 
-pub fn command() -> Command {
-    Command::new(SOLC)
+/// The EVM version that `solc` targets, which determines the opcodes it may emit.
+/// Shanghai introduced PUSH0, and Cancun MCOPY, TLOAD and TSTORE.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvmVersion {
+    London,
+    Paris,
+    Shanghai,
+    Cancun,
 }
 
-#[allow(dead_code)]
-pub fn is_available() -> bool {
-    let subprocess = command()
-        .arg("--version")
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn();
-    match subprocess {
-        Err(_) => false,
-        Ok(mut child) => match child.wait() {
-            Err(_) => false,
-            Ok(_) => true,
-        },
+impl EvmVersion {
+    pub fn as_str(self) -> &'static str {
+        use EvmVersion::*;
+        match self {
+            London => "london",
+            Paris => "paris",
+            Shanghai => "shanghai",
+            Cancun => "cancun",
+        }
+    }
+
+    /// The earliest `solc` release that supports this EVM version.
+    pub fn min_solc_version(self) -> SolcVersion {
+        use EvmVersion::*;
+        match self {
+            London => SolcVersion(0, 8, 7),
+            Paris => SolcVersion(0, 8, 18),
+            Shanghai => SolcVersion(0, 8, 20),
+            Cancun => SolcVersion(0, 8, 24),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SolcVersion(pub u32, pub u32, pub u32);
+
+impl fmt::Display for SolcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+impl SolcVersion {
+    /// Parses the output of `solc --version`, which ends with a line
+    /// like `Version: 0.8.20+commit.a1b79de6.Linux.g++`.
+    pub fn parse(output: &str) -> Option<SolcVersion> {
+        let version = output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Version: "))?;
+        let version = version.split(['+', '-']).next()?;
+        let mut numbers = version.split('.').map(|n| n.parse::<u32>().ok());
+        match (numbers.next(), numbers.next(), numbers.next()) {
+            (Some(Some(major)), Some(Some(minor)), Some(Some(patch))) => {
+                Some(SolcVersion(major, minor, patch))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How to run `solc`, with the options shared by the Solidity and Yul frontends.
+#[derive(Clone, Debug)]
+pub struct Solc {
+    pub path: PathBuf,
+    pub optimizer_runs: u32,
+    pub via_ir: bool,
+    /// Import remappings, like `@openzeppelin/=lib/openzeppelin-contracts/`
+    pub remappings: Vec<String>,
+    pub base_path: Option<PathBuf>,
+    pub include_paths: Vec<PathBuf>,
+}
+
+/// Compiles Solidity code with `solc`.
+pub struct Solidity {
+    pub solc: Solc,
+    pub evm_version: EvmVersion,
+    /// The contract to compile, which may be omitted if the file
+    /// defines only one contract that can be deployed
+    pub contract_name: Option<String>,
+}
+
+/// The output of `solc --standard-json`, restricted to what we ask for.
+/// See: https://docs.soliditylang.org/en/develop/using-the-compiler.html#output-description
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct StandardOutput {
+    #[serde(default)]
+    errors: Vec<StandardError>,
+    /// The contracts of each source file, by name
+    #[serde(default)]
+    pub(crate) contracts: HashMap<String, BTreeMap<String, StandardContract>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all(deserialize = "camelCase"))]
+struct StandardError {
+    severity: String,
+    message: String,
+    formatted_message: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub(crate) struct StandardContract {
+    #[serde(default)]
+    abi: Functions,
+    pub(crate) evm: StandardEvm,
+    #[serde(default)]
+    storage_layout: StorageLayout,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub(crate) struct StandardEvm {
+    /// Only asked for Yul objects, for which it is the creation code
+    #[serde(default)]
+    pub(crate) bytecode: StandardBytecode,
+    /// Only asked for Yul objects, to tell whether they have sub-objects
+    #[serde(default)]
+    pub(crate) assembly: String,
+    /// Missing for Yul objects without a runtime sub-object
+    #[serde(default)]
+    pub(crate) deployed_bytecode: StandardBytecode,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct StandardBytecode {
+    /// The bytecode in hexadecimal, which is empty for interfaces and abstract contracts
+    pub(crate) object: String,
+}
+
+impl StandardOutput {
+    /// Fails with the errors that `solc` reported, if any, leaving out warnings.
+    pub(crate) fn check_errors(&mut self) -> Result<(), FrontendError> {
+        let errors: Vec<String> = std::mem::take(&mut self.errors)
+            .into_iter()
+            .filter(|error| error.severity == "error")
+            .map(|error| error.formatted_message.unwrap_or(error.message))
+            .collect();
+        if !errors.is_empty() {
+            return Err(FrontendError::Diagnostics(errors.join("\n")));
+        }
+        Ok(())
+    }
+}
+
+impl Solc {
+    pub fn command(&self) -> Command {
+        Command::new(&self.path)
+    }
+
+    /// The name of the program, for messages
+    pub fn name(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    pub fn version(&self) -> Result<SolcVersion, FrontendError> {
+        let mut command = self.command();
+        command.arg("--version");
+        let output = execute(command)?;
+        let output = String::from_utf8_lossy(&output);
+        SolcVersion::parse(&output).ok_or(FrontendError::UnexpectedOutput)
+    }
+
+    /// Checks that this `solc` is recent enough to target the given EVM version.
+    pub fn check_version(&self, evm_version: EvmVersion) -> Result<(), FrontendError> {
+        let version = self.version()?;
+        let min_version = evm_version.min_solc_version();
+        if version < min_version {
+            return Err(FrontendError::UnsupportedVersion(
+                version.to_string(),
+                min_version.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The path of the given file as `solc` knows it, which is relative
+    /// to the base path, if the file is within it.
+    pub(crate) fn source_name(&self, input_path: &Path) -> String {
+        let relative_path = self.base_path.as_ref().and_then(|base_path| {
+            let base_path = base_path.canonicalize().ok()?;
+            let input_path = input_path.canonicalize().ok()?;
+            Some(input_path.strip_prefix(base_path).ok()?.to_path_buf())
+        });
+        relative_path
+            .as_deref()
+            .unwrap_or(input_path)
+            .to_string_lossy()
+            .into_owned()
     }
 }
 
-pub fn execute<I, S>(input_path: &Path, args: I) -> Result<Output, CompileError>
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<OsStr>,
-{
-    let subprocess = command()
-        .args(args)
-        .arg(input_path.as_os_str())
-        .stdin(Stdio::null())
+/// The directory of the given source file, which `solc` must be allowed to read.
+pub(crate) fn allow_path(input_path: &Path) -> &Path {
+    match input_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Runs `solc --standard-json` on the given input, allowing it to read the
+/// source files in the given directory.
+pub(crate) fn execute_standard_json(
+    solc: &Solc,
+    allow_path: &Path,
+    input: &serde_json::Value,
+) -> Result<StandardOutput, FrontendError> {
+    let mut command = solc.command();
+    command
+        .arg("--standard-json")
+        .arg("--allow-paths")
+        .arg(allow_path.as_os_str());
+    if let Some(base_path) = &solc.base_path {
+        command.arg("--base-path").arg(base_path);
+    }
+    for include_path in &solc.include_paths {
+        command.arg("--include-path").arg(include_path);
+    }
+    let subprocess = command
+        .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn();
-    match subprocess {
-        Err(_err) => Err(CompileError::ProgramSpawn),
-        Ok(child) => match child.wait_with_output() {
-            Err(_) => Err(CompileError::ProgramWait),
-            Ok(output) => Ok(output),
-        },
+    let mut child = match subprocess {
+        Err(_err) => return Err(FrontendError::ProgramSpawn),
+        Ok(child) => child,
+    };
+    // Dropping stdin closes it, so that `solc` sees the end of the input
+    if let Some(mut stdin) = child.stdin.take() {
+        if stdin.write_all(input.to_string().as_bytes()).is_err() {
+            return Err(FrontendError::ProgramSpawn);
+        }
     }
-}
-
-pub fn compile(input_path: &Path) -> Result<Program, CompileError> {
-    let output = execute(
-        input_path,
-        [
-            "--evm-version",
-            "paris",
-            "--bin-runtime",
-            "--optimize",
-            "--metadata-hash",
-            "none",
-        ],
-    )?;
+    let output = match child.wait_with_output() {
+        Err(_) => return Err(FrontendError::ProgramWait),
+        Ok(output) => output,
+    };
     match output.status.code() {
         Some(0) => {
-            let output = String::from_utf8_lossy(&output.stdout);
-            //let marker = "Binary:\n";
-            let marker = "Binary of the runtime part:\n";
-            match output.find(marker) {
-                None => Err(CompileError::UnexpectedOutput),
-                Some(pos) => match decode_bytecode(&output[pos + marker.len()..]) {
-                    Err(err) => Err(CompileError::Decode(err)),
-                    Ok(program) => Ok(program),
+            serde_json::from_slice(&output.stdout).map_err(|_| FrontendError::UnexpectedOutput)
+        }
+        Some(code) => Err(FrontendError::UnexpectedExit(code, output.stderr)),
+        None => Err(FrontendError::UnexpectedSignal(output.stderr)),
+    }
+}
+
+impl Frontend for Solidity {
+    fn language(&self) -> &'static str {
+        "Solidity"
+    }
+
+    fn program(&self) -> String {
+        self.solc.name()
+    }
+
+    fn compile_source(&self, input_path: &Path) -> Result<Contract, FrontendError> {
+        self.solc.check_version(self.evm_version)?;
+        let source_name = self.solc.source_name(input_path);
+        let input = serde_json::json!({
+            "language": "Solidity",
+            "sources": {
+                &source_name: { "urls": [&source_name] },
+            },
+            "settings": {
+                "evmVersion": self.evm_version.as_str(),
+                "optimizer": { "enabled": true, "runs": self.solc.optimizer_runs },
+                "viaIR": self.solc.via_ir,
+                "remappings": self.solc.remappings,
+                "metadata": { "bytecodeHash": "none" },
+                "outputSelection": {
+                    &source_name: {
+                        "*": ["abi", "evm.deployedBytecode.object", "storageLayout"],
+                    },
                 },
-            }
+            },
+        });
+        let output = execute_standard_json(&self.solc, allow_path(input_path), &input)?;
+        select_contract(output, &source_name, self.contract_name.as_deref())
+    }
+}
+
+fn select_contract(
+    mut output: StandardOutput,
+    source_name: &str,
+    contract_name: Option<&str>,
+) -> Result<Contract, FrontendError> {
+    output.check_errors()?;
+
+    let mut contracts: BTreeMap<String, StandardContract> = output
+        .contracts
+        .remove(source_name)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, contract)| !contract.evm.deployed_bytecode.object.is_empty())
+        .collect();
+    let name = match contract_name {
+        Some(name) if contracts.contains_key(name) => name.to_string(),
+        Some(name) => {
+            return Err(FrontendError::UnknownContract(
+                name.to_string(),
+                contracts.into_keys().collect(),
+            ))
+        }
+        None if contracts.len() == 1 => contracts.keys().next().unwrap().clone(),
+        None => {
+            return Err(FrontendError::AmbiguousContract(
+                contracts.into_keys().collect(),
+            ))
         }
-        Some(code) => Err(CompileError::UnexpectedExit(code, output.stderr)),
-        None => Err(CompileError::UnexpectedSignal(output.stderr)),
+    };
+    // Unwrap is safe because the name was found above
+    let contract = contracts.remove(&name).unwrap();
+    match decode_bytecode(&contract.evm.deployed_bytecode.object) {
+        Err(err) => Err(FrontendError::Decode(err)),
+        Ok((program, metadata)) => Ok(Contract {
+            name,
+            program,
+            metadata,
+            abi: contract.abi,
+            storage_layout: Some(contract.storage_layout),
+        }),
     }
 }
 
-pub fn compile_abi(input_path: &Path) -> Result<Functions, CompileError> {
-    let output = execute(input_path, ["--abi"])?;
-    match output.status.code() {
-        Some(0) => {
-            let output = String::from_utf8_lossy(&output.stdout);
-            let marker = "Contract JSON ABI\n";
-            match output.find(marker) {
-                None => Err(CompileError::UnexpectedOutput),
-                Some(pos) => crate::abi::parse_str(&output[pos + marker.len()..])
-                    .map_err(|_| CompileError::UnexpectedOutput),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static OUTPUT: &str = r#"{
+        "contracts": {
+            "test/calc.sol": {
+                "Calc": {
+                    "abi": [],
+                    "evm": {"deployedBytecode": {"object": "6080604052"}},
+                    "storageLayout": {"storage": [], "types": null}
+                },
+                "ICalc": {
+                    "abi": [],
+                    "evm": {"deployedBytecode": {"object": ""}},
+                    "storageLayout": {"storage": [], "types": null}
+                },
+                "Math": {
+                    "abi": [],
+                    "evm": {"deployedBytecode": {"object": "6080604052"}},
+                    "storageLayout": {"storage": [], "types": null}
+                }
             }
-        }
-        Some(code) => Err(CompileError::UnexpectedExit(code, output.stderr)),
-        None => Err(CompileError::UnexpectedSignal(output.stderr)),
+        },
+        "errors": [
+            {"severity": "warning", "message": "unused variable"}
+        ]
+    }"#;
+
+    fn output() -> StandardOutput {
+        serde_json::from_str(OUTPUT).unwrap()
+    }
+
+    #[test]
+    fn test_select_contract() {
+        let contract = select_contract(output(), "test/calc.sol", Some("Calc")).unwrap();
+        assert_eq!(contract.name, "Calc");
+
+        assert_eq!(
+            select_contract(output(), "test/calc.sol", None).err(),
+            Some(FrontendError::AmbiguousContract(vec![
+                "Calc".to_string(),
+                "Math".to_string()
+            ]))
+        );
+        // Interfaces cannot be deployed
+        assert_eq!(
+            select_contract(output(), "test/calc.sol", Some("ICalc")).err(),
+            Some(FrontendError::UnknownContract(
+                "ICalc".to_string(),
+                vec!["Calc".to_string(), "Math".to_string()]
+            ))
+        );
+        assert_eq!(
+            select_contract(output(), "other.sol", None).err(),
+            Some(FrontendError::AmbiguousContract(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_select_contract_errors() {
+        let mut output = output();
+        output.errors.push(StandardError {
+            severity: "error".to_string(),
+            message: "bad".to_string(),
+            formatted_message: Some("ParserError: bad".to_string()),
+        });
+        assert_eq!(
+            select_contract(output, "test/calc.sol", Some("Calc")).err(),
+            Some(FrontendError::Diagnostics("ParserError: bad".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_solc_version() {
+        let output = "solc, the solidity compiler commandline interface\nVersion: 0.8.20+commit.a1b79de6.Linux.g++\n";
+        assert_eq!(SolcVersion::parse(output), Some(SolcVersion(0, 8, 20)));
+        assert_eq!(
+            SolcVersion::parse("Version: 0.8.24-develop.2024.1.1+commit.deadbeef"),
+            Some(SolcVersion(0, 8, 24))
+        );
+        assert_eq!(SolcVersion::parse("solc 0.8.20"), None);
+        assert!(SolcVersion(0, 8, 9) < EvmVersion::Paris.min_solc_version());
+        assert!(SolcVersion(0, 10, 0) > EvmVersion::Cancun.min_solc_version());
     }
 }
@@ -0,0 +1,291 @@
+// This is free and unencumbered software released into the public domain.
+
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// The storage layout of a contract, as output by `solc --storage-layout`.
+/// See: https://docs.soliditylang.org/en/develop/internals/layout_in_storage.html#json-output
+#[derive(Deserialize, Debug, PartialEq, Eq, Default)]
+pub struct StorageLayout {
+    pub storage: Vec<StorageEntry>,
+    pub types: Option<HashMap<String, StorageType>>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StorageEntry {
+    pub label: String,
+    pub offset: u32,
+    /// A decimal number, since slots may not fit in 64 bits
+    pub slot: String,
+    /// The type identifier, which is described in `types`
+    pub r#type: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct StorageType {
+    pub label: String,
+    pub number_of_bytes: String,
+    pub encoding: Option<String>,
+    /// The fields of a struct, at slots relative to the struct's own
+    pub members: Option<Vec<StorageEntry>>,
+    /// The key type identifier of a mapping
+    pub key: Option<String>,
+    /// The value type identifier of a mapping
+    pub value: Option<String>,
+    /// The element type identifier of an array
+    pub base: Option<String>,
+}
+
+/// Why a contract cannot be upgraded to another one without corrupting its state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    Removed(StorageEntry),
+    /// The variable, with the labels of its old and new types, or of the first
+    /// of their members, keys, values or elements that differ
+    Retyped(String, String, String),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use LayoutError::*;
+        match self {
+            Removed(entry) => write!(
+                f,
+                "`{}' (slot {}, offset {}) was removed or moved",
+                entry.label, entry.slot, entry.offset
+            ),
+            Retyped(label, old, new) => {
+                write!(f, "`{}' changed type from `{}' to `{}'", label, old, new)
+            }
+        }
+    }
+}
+
+impl StorageLayout {
+    /// The human-readable name of a type, which unlike its identifier
+    /// does not depend on AST IDs, and so is stable across compilations.
+    fn type_label<'a>(&'a self, type_id: &'a str) -> &'a str {
+        self.types
+            .as_ref()
+            .and_then(|types| types.get(type_id))
+            .map_or(type_id, |t| &t.label)
+    }
+
+    fn type_info(&self, type_id: &str) -> Option<&StorageType> {
+        self.types.as_ref()?.get(type_id)
+    }
+
+    /// Compares a type of this layout with one of the new layout, down to the
+    /// members of structs and the keys, values and elements of containers.
+    /// Returns the labels of the first types that differ, if any. Types that
+    /// are already being compared are taken to be equal, as they may be recursive.
+    fn type_diff<'a>(
+        &'a self,
+        old_id: &'a str,
+        new: &'a StorageLayout,
+        new_id: &'a str,
+        seen: &mut HashSet<(&'a str, &'a str)>,
+    ) -> Option<(&'a str, &'a str)> {
+        let labels = (self.type_label(old_id), new.type_label(new_id));
+        if !seen.insert((old_id, new_id)) {
+            return None;
+        }
+        let (old_type, new_type) = match (self.type_info(old_id), new.type_info(new_id)) {
+            (Some(old_type), Some(new_type)) => (old_type, new_type),
+            (None, None) if labels.0 == labels.1 => return None,
+            _ => return Some(labels),
+        };
+        if old_type.label != new_type.label
+            || old_type.number_of_bytes != new_type.number_of_bytes
+            || old_type.encoding != new_type.encoding
+        {
+            return Some(labels);
+        }
+        let elements = [
+            (&old_type.key, &new_type.key),
+            (&old_type.value, &new_type.value),
+            (&old_type.base, &new_type.base),
+        ];
+        for element in elements {
+            match element {
+                (Some(old_id), Some(new_id)) => {
+                    let diff = self.type_diff(old_id, new, new_id, seen);
+                    if diff.is_some() {
+                        return diff;
+                    }
+                }
+                (None, None) => {}
+                _ => return Some(labels),
+            }
+        }
+        match (&old_type.members, &new_type.members) {
+            (Some(old_members), Some(new_members)) => {
+                if old_members.len() != new_members.len() {
+                    return Some(labels);
+                }
+                for (old_member, new_member) in old_members.iter().zip(new_members) {
+                    if old_member.slot != new_member.slot || old_member.offset != new_member.offset
+                    {
+                        return Some(labels);
+                    }
+                    let diff = self.type_diff(&old_member.r#type, new, &new_member.r#type, seen);
+                    if diff.is_some() {
+                        return diff;
+                    }
+                }
+                None
+            }
+            (None, None) => None,
+            _ => Some(labels),
+        }
+    }
+
+    /// Checks that the state of a contract with this layout can be read by a
+    /// contract with the given layout: every variable must keep its slot, offset
+    /// and type. Variables may be renamed, and new ones appended after them.
+    pub fn check_upgrade(&self, new: &StorageLayout) -> Result<(), LayoutError> {
+        for old_entry in self.storage.iter() {
+            let new_entry = new
+                .storage
+                .iter()
+                .find(|e| e.slot == old_entry.slot && e.offset == old_entry.offset)
+                .ok_or_else(|| LayoutError::Removed(old_entry.clone()))?;
+            let mut seen = HashSet::new();
+            let diff = self.type_diff(&old_entry.r#type, new, &new_entry.r#type, &mut seen);
+            if let Some((old_type, new_type)) = diff {
+                return Err(LayoutError::Retyped(
+                    old_entry.label.clone(),
+                    old_type.to_string(),
+                    new_type.to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_str(json: &str) -> Result<StorageLayout, serde_json::Error> {
+    serde_json::from_str::<StorageLayout>(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static COUNTER: &str = r#"{
+        "storage": [
+            {"astId":3,"contract":"Counter.sol:Counter","label":"owner","offset":0,"slot":"0","type":"t_address"},
+            {"astId":5,"contract":"Counter.sol:Counter","label":"paused","offset":20,"slot":"0","type":"t_bool"},
+            {"astId":7,"contract":"Counter.sol:Counter","label":"count","offset":0,"slot":"1","type":"t_uint256"}
+        ],
+        "types": {
+            "t_address":{"encoding":"inplace","label":"address","numberOfBytes":"20"},
+            "t_bool":{"encoding":"inplace","label":"bool","numberOfBytes":"1"},
+            "t_uint256":{"encoding":"inplace","label":"uint256","numberOfBytes":"32"}
+        }
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let layout = parse_str(COUNTER).unwrap();
+        assert_eq!(layout.storage.len(), 3);
+        assert_eq!(
+            layout.storage[1],
+            StorageEntry {
+                label: "paused".to_string(),
+                offset: 20,
+                slot: "0".to_string(),
+                r#type: "t_bool".to_string(),
+            }
+        );
+        assert_eq!(layout.type_label("t_bool"), "bool");
+        assert_eq!(
+            parse_str(r#"{"storage":[]}"#).unwrap(),
+            StorageLayout::default()
+        );
+    }
+
+    #[test]
+    fn test_check_upgrade() {
+        let old = parse_str(COUNTER).unwrap();
+        assert_eq!(old.check_upgrade(&old), Ok(()));
+
+        // Appending and renaming variables is fine
+        let mut new = parse_str(COUNTER).unwrap();
+        new.storage[2].label = "counter".to_string();
+        new.storage.push(StorageEntry {
+            label: "limit".to_string(),
+            offset: 0,
+            slot: "2".to_string(),
+            r#type: "t_uint256".to_string(),
+        });
+        assert_eq!(old.check_upgrade(&new), Ok(()));
+        assert!(new.check_upgrade(&old).is_err());
+
+        let mut new = parse_str(COUNTER).unwrap();
+        new.storage.remove(1);
+        assert_eq!(
+            old.check_upgrade(&new),
+            Err(LayoutError::Removed(old.storage[1].clone()))
+        );
+
+        let mut new = parse_str(COUNTER).unwrap();
+        new.storage[2].r#type = "t_address".to_string();
+        assert_eq!(
+            old.check_upgrade(&new).unwrap_err().to_string(),
+            "`count' changed type from `uint256' to `address'"
+        );
+    }
+
+    static BALANCES: &str = r#"{
+        "storage": [
+            {"astId":9,"contract":"Token.sol:Token","label":"accounts","offset":0,"slot":"0","type":"t_mapping(t_address,t_struct(Account)5_storage)"}
+        ],
+        "types": {
+            "t_address":{"encoding":"inplace","label":"address","numberOfBytes":"20"},
+            "t_array(t_uint128)dyn_storage":{"base":"t_uint128","encoding":"dynamic_array","label":"uint128[]","numberOfBytes":"32"},
+            "t_mapping(t_address,t_struct(Account)5_storage)":{"encoding":"mapping","key":"t_address","label":"mapping(address => struct Token.Account)","numberOfBytes":"32","value":"t_struct(Account)5_storage"},
+            "t_struct(Account)5_storage":{"encoding":"inplace","label":"struct Token.Account","members":[
+                {"astId":1,"contract":"Token.sol:Token","label":"balance","offset":0,"slot":"0","type":"t_uint128"},
+                {"astId":4,"contract":"Token.sol:Token","label":"history","offset":0,"slot":"1","type":"t_array(t_uint128)dyn_storage"}
+            ],"numberOfBytes":"64"},
+            "t_uint128":{"encoding":"inplace","label":"uint128","numberOfBytes":"16"},
+            "t_uint64":{"encoding":"inplace","label":"uint64","numberOfBytes":"8"}
+        }
+    }"#;
+
+    fn balance_member(layout: &mut StorageLayout) -> &mut StorageEntry {
+        let types = layout.types.as_mut().unwrap();
+        let account = types.get_mut("t_struct(Account)5_storage").unwrap();
+        &mut account.members.as_mut().unwrap()[0]
+    }
+
+    #[test]
+    fn test_check_upgrade_nested() {
+        let old = parse_str(BALANCES).unwrap();
+        assert_eq!(old.check_upgrade(&old), Ok(()));
+
+        // Struct members may be renamed, but not retyped
+        let mut new = parse_str(BALANCES).unwrap();
+        balance_member(&mut new).label = "amount".to_string();
+        assert_eq!(old.check_upgrade(&new), Ok(()));
+        balance_member(&mut new).r#type = "t_uint64".to_string();
+        assert_eq!(
+            old.check_upgrade(&new).unwrap_err().to_string(),
+            "`accounts' changed type from `uint128' to `uint64'"
+        );
+
+        // Nor may array elements, even with the same label
+        let mut new = parse_str(BALANCES).unwrap();
+        let types = new.types.as_mut().unwrap();
+        types.get_mut("t_array(t_uint128)dyn_storage").unwrap().base = Some("t_uint64".to_string());
+        assert_eq!(
+            old.check_upgrade(&new).unwrap_err().to_string(),
+            "`accounts' changed type from `uint128' to `uint64'"
+        );
+    }
+}
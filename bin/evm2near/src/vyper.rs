@@ -0,0 +1,91 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::{path::Path, process::Command};
+
+use crate::{
+    abi,
+    decode::decode_bytecode,
+    error::FrontendError,
+    frontend::{contract_name, execute, Contract, Frontend},
+    solidity::EvmVersion,
+};
+
+pub const VYPER: &str = "vyper";
+
+/// Compiles Vyper code with `vyper`.
+pub struct Vyper {
+    pub evm_version: EvmVersion,
+}
+
+pub fn command() -> Command {
+    Command::new(VYPER)
+}
+
+impl Frontend for Vyper {
+    fn language(&self) -> &'static str {
+        "Vyper"
+    }
+
+    fn program(&self) -> String {
+        VYPER.to_string()
+    }
+
+    fn compile_source(&self, input_path: &Path) -> Result<Contract, FrontendError> {
+        let mut command = command();
+        command
+            .arg("-f")
+            .arg("bytecode_runtime,abi")
+            .arg("--evm-version")
+            .arg(self.evm_version.as_str())
+            .arg(input_path);
+        let output = execute(command)?;
+        parse_output(&output, contract_name(input_path))
+    }
+}
+
+/// Parses the output of `vyper -f bytecode_runtime,abi`, which prints each
+/// requested format on its own line: the bytecode in hexadecimal, then the ABI.
+fn parse_output(output: &[u8], name: String) -> Result<Contract, FrontendError> {
+    let output = std::str::from_utf8(output).map_err(|_| FrontendError::UnexpectedOutput)?;
+    let mut lines = output.lines();
+    let (bytecode, abi) = match (lines.next(), lines.next()) {
+        (Some(bytecode), Some(abi)) => (bytecode, abi),
+        _ => return Err(FrontendError::UnexpectedOutput),
+    };
+    let (program, metadata) =
+        decode_bytecode(bytecode.trim_start_matches("0x")).map_err(FrontendError::Decode)?;
+    let abi = abi::parse_str(abi).map_err(|_| FrontendError::UnexpectedOutput)?;
+    Ok(Contract {
+        name,
+        program,
+        metadata,
+        abi,
+        storage_layout: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static OUTPUT: &str = concat!(
+        "0x6003361161000c57\n",
+        r#"[{"stateMutability": "view", "type": "function", "name": "count", "inputs": [], "outputs": [{"name": "", "type": "uint256"}]}, {"anonymous": false, "inputs": [], "name": "Incremented", "type": "event"}]"#,
+        "\n",
+    );
+
+    #[test]
+    fn test_parse_output() {
+        let contract = parse_output(OUTPUT.as_bytes(), "Counter".to_string()).unwrap();
+        assert_eq!(contract.name, "Counter");
+        let (program, _) = decode_bytecode("6003361161000c57").unwrap();
+        assert!(contract.program.0 == program.0);
+        let names: Vec<String> = contract.abi.into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["count"]);
+
+        assert!(matches!(
+            parse_output(b"0x6003361161000c57\n", "Counter".to_string()),
+            Err(FrontendError::UnexpectedOutput)
+        ));
+    }
+}
@@ -1,4 +1,3 @@
-use anyhow::Result;
 use wasm_encoder::{
     CodeSection, DataSection, DataSegment, DataSegmentMode, ElementSection, EntityType, ExportKind,
     ExportSection, Function, FunctionSection, GlobalSection, GlobalType, ImportSection,
@@ -7,18 +6,20 @@ use wasm_encoder::{
 };
 use wasmparser::Payload;
 
-use crate::wasm_translate::translator::*;
+use crate::{error::TranslateError, wasm_translate::translator::*};
+
+type Result<T, E = TranslateError> = std::result::Result<T, E>;
 
 pub type Params = Vec<ValType>;
 pub type Results = Vec<ValType>;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Signature {
     pub params: Params,
     pub results: Results,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Import {
     module: String,
     field: String,
@@ -27,20 +28,20 @@ pub struct Import {
 
 pub type TypeIndex = u32;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Glob<'a> {
     pub global_type: GlobalType,
     pub init_instr: Instruction<'a>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Export {
     pub name: String,
     pub kind: ExportKind,
     pub index: u32,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ModuleBuilder<'a> {
     pub types: Vec<Signature>,
     pub imports: Vec<Import>,
@@ -56,7 +57,7 @@ pub struct ModuleBuilder<'a> {
 }
 
 impl<'a> ModuleBuilder<'a> {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         ModuleBuilder {
             types: Default::default(),
             imports: Default::default(),
@@ -187,11 +188,10 @@ impl<'a> ModuleBuilder<'a> {
     }
 }
 
-pub fn parse(wasm: &Vec<u8>) -> Result<ModuleBuilder> {
+pub fn parse(wasm: &[u8]) -> Result<ModuleBuilder> {
     let parsed = wasmparser::Parser::new(0)
-        .parse_all(wasm.as_slice())
-        .map(|p| p.unwrap())
-        .collect::<Vec<_>>();
+        .parse_all(wasm)
+        .collect::<Result<Vec<_>, _>>()?;
 
     let mut code_section_size: Option<u32> = None;
 
@@ -203,7 +203,11 @@ pub fn parse(wasm: &Vec<u8>) -> Result<ModuleBuilder> {
                 encoding,
                 range: _range,
             } => {
-                assert_eq!(encoding, wasmparser::Encoding::Module);
+                if encoding != wasmparser::Encoding::Module {
+                    return Err(TranslateError::Unsupported(
+                        "only wasm modules are supported, not components",
+                    ));
+                }
             }
             Payload::TypeSection(type_section) => {
                 for typ in type_section {
@@ -232,7 +236,7 @@ pub fn parse(wasm: &Vec<u8>) -> Result<ModuleBuilder> {
                     let table = table?;
                     if let wasmparser::TableInit::RefNull = table.init {
                     } else {
-                        panic!("unknown table init");
+                        return Err(TranslateError::Unsupported("unknown table init"));
                     }
                     let table_type = table_type(&table.ty)?;
                     builder.tables.push(table_type);
@@ -246,10 +250,11 @@ pub fn parse(wasm: &Vec<u8>) -> Result<ModuleBuilder> {
                 }
             }
             Payload::TagSection(tag_section) => {
-                for tag in tag_section {
-                    let tag = tag?;
-                    let _tag_type = tag_type(&tag)?;
-                    panic!("only wasm core - 1 specification is supported by near runtime");
+                if let Some(tag) = tag_section.into_iter().next() {
+                    let _tag_type = tag_type(&tag?)?;
+                    return Err(TranslateError::Unsupported(
+                        "only wasm core - 1 specification is supported by near runtime",
+                    ));
                     // https://github.com/near/nearcore/issues/8358#issuecomment-1383247423
                 }
             }
@@ -286,7 +291,9 @@ pub fn parse(wasm: &Vec<u8>) -> Result<ModuleBuilder> {
                 }
             }
             Payload::DataCountSection { count: _, range: _ } => {
-                panic!("only wasm core - 1 specification is supported by near runtime");
+                return Err(TranslateError::Unsupported(
+                    "only wasm core - 1 specification is supported by near runtime",
+                ));
                 // https://github.com/near/nearcore/issues/8358#issuecomment-1383247423
             }
             Payload::DataSection(data_section) => {
@@ -303,61 +310,105 @@ pub fn parse(wasm: &Vec<u8>) -> Result<ModuleBuilder> {
                 code_section_size = Some(count);
             }
             Payload::CodeSectionEntry(code_section_entry) => {
-                assert!(code_section_size.is_some());
+                if code_section_size.is_none() {
+                    return Err(TranslateError::Invalid(
+                        "code section entry before the code section start",
+                    ));
+                }
                 let code_seg = code(code_section_entry)?;
                 builder.code.push(code_seg);
             }
             Payload::ModuleSection {
                 parser: _,
                 range: _,
-            } => unimplemented!("unsupported section (ModuleSection)"),
-            Payload::InstanceSection(_) => unimplemented!("unsupported section (InstanceSection)"),
-            Payload::CoreTypeSection(_) => unimplemented!("unsupported section (CoreTypeSection)"),
+            } => {
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ModuleSection)",
+                ))
+            }
+            Payload::InstanceSection(_) => {
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (InstanceSection)",
+                ))
+            }
+            Payload::CoreTypeSection(_) => {
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (CoreTypeSection)",
+                ))
+            }
             Payload::ComponentSection {
                 parser: _,
                 range: _,
-            } => unimplemented!("unsupported section (ComponentSection)"),
+            } => {
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentSection)",
+                ))
+            }
             Payload::ComponentInstanceSection(_) => {
-                unimplemented!("unsupported section (ComponentInstanceSection)")
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentInstanceSection)",
+                ))
             }
             Payload::ComponentAliasSection(_) => {
-                unimplemented!("unsupported section (ComponentAliasSection)")
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentAliasSection)",
+                ))
             }
             Payload::ComponentTypeSection(_) => {
-                unimplemented!("unsupported section (ComponentTypeSection)")
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentTypeSection)",
+                ))
             }
             Payload::ComponentCanonicalSection(_) => {
-                unimplemented!("unsupported section (ComponentCanonicalSection)")
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentCanonicalSection)",
+                ))
             }
             Payload::ComponentStartSection { start: _, range: _ } => {
-                unimplemented!("unsupported section (ComponentStartSection)")
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentStartSection)",
+                ))
             }
             Payload::ComponentImportSection(_) => {
-                unimplemented!("unsupported section (ComponentImportSection)")
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentImportSection)",
+                ))
             }
             Payload::ComponentExportSection(_) => {
-                unimplemented!("unsupported section (ComponentExportSection)")
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (ComponentExportSection)",
+                ))
+            }
+            Payload::CustomSection(_) => {
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (CustomSection)",
+                ))
             }
-            Payload::CustomSection(_) => unimplemented!("unsupported section (CustomSection)"),
             Payload::UnknownSection {
                 id: _,
                 contents: _,
                 range: _,
-            } => unimplemented!("unsupported section (UnknownSection)"),
+            } => {
+                return Err(TranslateError::Unsupported(
+                    "unsupported section (UnknownSection)",
+                ))
+            }
             Payload::End(_end) => {}
         }
     }
 
-    assert_eq!(
-        u32::try_from(builder.code.len()).unwrap(),
-        code_section_size.unwrap()
-    );
+    if u32::try_from(builder.code.len()).ok() != code_section_size {
+        return Err(TranslateError::Invalid(
+            "code section size does not match its entries",
+        ));
+    }
 
     Ok(builder)
 }
 
 pub(crate) mod translator {
-    use anyhow::{Error, Result};
+    use super::Result;
+    use crate::error::TranslateError;
     use wasm_encoder::*;
     use wasmparser::{for_each_operator, ExternalKind, FunctionBody, Operator, Type};
 
@@ -367,7 +418,7 @@ pub(crate) mod translator {
         ConstExpr::raw(const_expr_buf)
     }
 
-    #[derive(Debug)]
+    #[derive(Clone, Debug)]
     pub struct ElementSegment {
         pub mode: ElementMode,
         pub element_type: RefType,
@@ -394,6 +445,27 @@ pub(crate) mod translator {
         },
     }
 
+    /// `wasm_encoder::ConstExpr` is not `Clone`, so it is cloned through its encoding.
+    fn clone_const_expr(expr: &ConstExpr) -> ConstExpr {
+        let mut bytes = Vec::new();
+        expr.encode(&mut bytes);
+        bytes.pop(); // the `end` that encoding appends
+        ConstExpr::raw(bytes)
+    }
+
+    impl Clone for ElementMode {
+        fn clone(&self) -> Self {
+            match self {
+                Self::Passive => Self::Passive,
+                Self::Declared => Self::Declared,
+                Self::Active { table, offset } => Self::Active {
+                    table: *table,
+                    offset: clone_const_expr(offset),
+                },
+            }
+        }
+    }
+
     impl ElementMode {
         pub fn borrowed(&self) -> wasm_encoder::ElementMode {
             match self {
@@ -413,6 +485,17 @@ pub(crate) mod translator {
         Expressions(Vec<wasm_encoder::ConstExpr>),
     }
 
+    impl Clone for Elements {
+        fn clone(&self) -> Self {
+            match self {
+                Self::Functions(xs) => Self::Functions(xs.clone()),
+                Self::Expressions(xs) => {
+                    Self::Expressions(xs.iter().map(clone_const_expr).collect())
+                }
+            }
+        }
+    }
+
     impl Elements {
         pub fn borrowed(&self) -> wasm_encoder::Elements {
             match self {
@@ -422,7 +505,7 @@ pub(crate) mod translator {
         }
     }
 
-    #[derive(Debug)]
+    #[derive(Clone, Debug)]
     pub enum DataMode<'a> {
         Active {
             memory_index: u32,
@@ -431,7 +514,7 @@ pub(crate) mod translator {
         Passive,
     }
 
-    #[derive(Debug)]
+    #[derive(Clone, Debug)]
     pub struct Data<'a> {
         pub mode: DataMode<'a>,
         pub data: Vec<u8>,
@@ -551,7 +634,7 @@ pub(crate) mod translator {
         let operator = e.read()?;
         match e.read()? {
             Operator::End if e.eof() => {}
-            _ => return Err(Error::msg("invalid init expression")),
+            _ => return Err(TranslateError::Invalid("invalid init expression")),
         }
         op(&operator)
     }
@@ -564,7 +647,7 @@ pub(crate) mod translator {
             Instruction::RefNull(HeapType::Func)
             | Instruction::RefFunc(_)
             | Instruction::GlobalGet(_) => {}
-            _ => return Err(Error::msg("no mutations applicable")),
+            _ => return Err(TranslateError::Unsupported("no mutations applicable")),
         }
 
         Ok(constexpr_from_instr(instr))
@@ -0,0 +1,151 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::path::Path;
+
+use crate::{
+    abi::Functions,
+    decode::decode_bytecode,
+    error::FrontendError,
+    frontend::{contract_name, Contract, Frontend},
+    solidity::{allow_path, execute_standard_json, EvmVersion, Solc, StandardOutput},
+};
+
+/// Compiles Yul code with `solc --standard-json`. An object with a single
+/// sub-object, as written for deployment, is compiled to the code of that
+/// sub-object, while any other code is taken to be the runtime code of the
+/// contract. Either way, it has no ABI.
+pub struct Yul {
+    pub solc: Solc,
+    pub evm_version: EvmVersion,
+}
+
+impl Frontend for Yul {
+    fn language(&self) -> &'static str {
+        "Yul"
+    }
+
+    fn program(&self) -> String {
+        self.solc.name()
+    }
+
+    fn compile_source(&self, input_path: &Path) -> Result<Contract, FrontendError> {
+        self.solc.check_version(self.evm_version)?;
+        let source_name = self.solc.source_name(input_path);
+        let input = serde_json::json!({
+            "language": "Yul",
+            "sources": {
+                &source_name: { "urls": [&source_name] },
+            },
+            "settings": {
+                "evmVersion": self.evm_version.as_str(),
+                "optimizer": { "enabled": true, "runs": self.solc.optimizer_runs },
+                "outputSelection": {
+                    &source_name: {
+                        "*": ["evm.assembly", "evm.bytecode.object", "evm.deployedBytecode.object"],
+                    },
+                },
+            },
+        });
+        let output = execute_standard_json(&self.solc, allow_path(input_path), &input)?;
+        select_object(output, &source_name, contract_name(input_path))
+    }
+}
+
+/// Takes the runtime code out of the output of `solc --standard-json`, which
+/// has the deployed bytecode of an object if it has a single sub-object.
+fn select_object(
+    mut output: StandardOutput,
+    source_name: &str,
+    name: String,
+) -> Result<Contract, FrontendError> {
+    output.check_errors()?;
+    let object = output
+        .contracts
+        .remove(source_name)
+        .and_then(|objects| objects.into_values().next())
+        .ok_or(FrontendError::UnexpectedOutput)?;
+    let evm = object.evm;
+    let bytecode = if !evm.deployed_bytecode.object.is_empty() {
+        evm.deployed_bytecode.object
+    } else if evm.assembly.contains("sub_0:") {
+        return Err(FrontendError::AmbiguousObject);
+    } else {
+        evm.bytecode.object
+    };
+    let (program, metadata) = decode_bytecode(&bytecode).map_err(FrontendError::Decode)?;
+    Ok(Contract {
+        name,
+        program,
+        metadata,
+        abi: Functions::default(),
+        storage_layout: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(json: &str) -> StandardOutput {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_select_object() {
+        let code = r#"{
+            "contracts": {
+                "test/answer.yul": {
+                    "object": {
+                        "evm": {
+                            "assembly": "    mstore(0x00, 0x2a)\n    return(0x00, 0x20)\n",
+                            "bytecode": {"object": "602a60005260206000f3"}
+                        }
+                    }
+                }
+            }
+        }"#;
+        let contract =
+            select_object(output(code), "test/answer.yul", "answer".to_string()).unwrap();
+        assert_eq!(contract.name, "answer");
+        let (program, _) = decode_bytecode("602a60005260206000f3").unwrap();
+        assert!(contract.program.0 == program.0);
+        assert_eq!(contract.abi, Functions::default());
+
+        assert!(matches!(
+            select_object(output(code), "other.yul", "other".to_string()),
+            Err(FrontendError::UnexpectedOutput)
+        ));
+    }
+
+    #[test]
+    fn test_select_runtime_object() {
+        // object "Answer" { code { ... } object "Answer_deployed" { code { ... } } }
+        let nested = r#"{
+            "contracts": {
+                "test/answer.yul": {
+                    "Answer": {
+                        "evm": {
+                            "assembly": "    codecopy(0x00, dataOffset(sub_0), dataSize(sub_0))\n    return(0x00, dataSize(sub_0))\nstop\n\nsub_0: assembly {\n    mstore(0x00, 0x2a)\n}\n",
+                            "bytecode": {"object": "600a600c600039600a6000f3fe602a60005260206000f3"},
+                            "deployedBytecode": {"object": "602a60005260206000f3"}
+                        }
+                    }
+                }
+            }
+        }"#;
+        let contract =
+            select_object(output(nested), "test/answer.yul", "answer".to_string()).unwrap();
+        let (program, _) = decode_bytecode("602a60005260206000f3").unwrap();
+        assert!(contract.program.0 == program.0);
+
+        // With several sub-objects, solc doesn't tell which one is deployed
+        let ambiguous = nested.replace(
+            r#""deployedBytecode": {"object": "602a60005260206000f3"}"#,
+            r#""deployedBytecode": {"object": ""}"#,
+        );
+        assert!(matches!(
+            select_object(output(&ambiguous), "test/answer.yul", "answer".to_string()),
+            Err(FrontendError::AmbiguousObject)
+        ));
+    }
+}
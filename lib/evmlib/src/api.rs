@@ -3,18 +3,25 @@
 use crate::{
     env::Env,
     json_utils::{decode::transform_json_call_data, encode::encode_return_data_as_json},
-    ops::{ENV, EVM},
-    state::Word,
+    ops::{Hasher, ENV, EVM},
+    registry::account_id_to_address,
+    state::{Word, ZERO},
 };
 
+// These must match the discriminants of `StateMutability` in the compiler.
+const STATE_MUTABILITY_PAYABLE: u32 = 1;
+const STATE_MUTABILITY_PURE: u32 = 2;
+const STATE_MUTABILITY_VIEW: u32 = 3;
+
 #[no_mangle]
 pub static mut _abi_buffer: [u8; 0xFFFF] = [1; 0xFFFF]; // FIXME
 
 #[no_mangle]
-pub unsafe fn _evm_init(_table_offset: u32, chain_id: u64, balance: u64) {
-    #[cfg(feature = "near")]
+#[allow(unused_variables)] // `value_scale` only applies to NEAR
+pub unsafe fn _evm_init(_table_offset: u32, chain_id: u64, value_scale: u8) {
+    #[cfg(all(feature = "near", not(test)))]
     {
-        // TODO
+        ENV.denomination = crate::near_runtime::Denomination::new(value_scale);
     }
 
     #[cfg(not(feature = "near"))]
@@ -60,15 +67,31 @@ pub unsafe fn _evm_init(_table_offset: u32, chain_id: u64, balance: u64) {
             }
         };
 
-        EVM.call_value = match args.next() {
-            None => crate::state::ZERO,
+        ENV.call_value = match args.next() {
+            None => ZERO,
             Some(s) => Word::from(s.parse::<u64>().unwrap_or(0)), // TODO: support decimal point as well
         };
-        //eprintln!("_evm_init: call_data={:?} call_value={:?}", ENV.call_data, EVM.call_value);
+        //eprintln!("_evm_init: call_data={:?} call_value={:?}", ENV.call_data, ENV.call_value);
     }
 
     EVM.chain_id = Word::from(chain_id);
-    EVM.self_balance = Word::from(balance);
+}
+
+/// Determines the value attached to the call, rejecting it early if the
+/// called method is not payable. Returns zero if the call was rejected.
+#[no_mangle]
+pub unsafe fn _evm_call_value(state_mutability: u32) -> u32 {
+    EVM.call_value = match state_mutability {
+        // NEAR prohibits reading the attached deposit in view calls, and
+        // a view or pure method cannot receive any value in the first place.
+        STATE_MUTABILITY_PURE | STATE_MUTABILITY_VIEW => ZERO,
+        _ => ENV.call_value(),
+    };
+    if state_mutability != STATE_MUTABILITY_PAYABLE && EVM.call_value != ZERO {
+        ENV.revert(&[]);
+        return 0;
+    }
+    1
 }
 
 #[no_mangle]
@@ -132,6 +155,91 @@ pub unsafe fn _evm_post_exec(
     ENV.post_exec();
 }
 
+/// Returns the EVM address of the NEAR account given as `{"account_id": "..."}`,
+/// encoded as a JSON string.
+#[no_mangle]
+pub unsafe fn _evm_address_of() {
+    let json_call_data: Option<serde_json::Value> = serde_json::from_slice(ENV.call_data()).ok();
+    let account_id = json_call_data
+        .as_ref()
+        .and_then(|json| json.get("account_id"))
+        .and_then(|account_id| account_id.as_str());
+    match account_id {
+        Some(account_id) => {
+            let address = account_id_to_address::<Hasher>(account_id.as_bytes());
+            ENV.value_return(format!(r#""0x{}""#, hex::encode(address)).as_bytes());
+        }
+        None => ENV.revert(br#"expected {"account_id": "..."}"#),
+    }
+    ENV.post_exec();
+}
+
+/// Moves the storage slots given as `{"slots": ["0x...", ...], "complete": false}`
+/// to the latest storage layout, returning the number of slots moved. Only the
+/// contract account itself may call this, since completing the migration early
+/// would hide the slots not moved yet.
+#[no_mangle]
+pub unsafe fn _evm_migrate() {
+    if ENV.caller() != ENV.address() {
+        ENV.revert(b"only the contract account may migrate its storage");
+        ENV.post_exec();
+        return;
+    }
+    let json_call_data: Option<serde_json::Value> = serde_json::from_slice(ENV.call_data()).ok();
+    let slots = json_call_data
+        .as_ref()
+        .and_then(|json| json.get("slots"))
+        .and_then(|slots| slots.as_array())
+        .and_then(|slots| {
+            slots
+                .iter()
+                .map(|slot| {
+                    let slot = slot.as_str()?;
+                    let slot = slot.strip_prefix("0x").unwrap_or(slot);
+                    Word::from_str_radix(slot, 16).ok()
+                })
+                .collect::<Option<Vec<_>>>()
+        });
+    let complete = json_call_data
+        .as_ref()
+        .map(|json| json.get("complete").map_or(Some(false), |c| c.as_bool()));
+    match (slots, complete.flatten()) {
+        (Some(slots), Some(complete)) => {
+            let moved = ENV.migrate_storage(&slots, complete);
+            ENV.value_return(moved.to_string().as_bytes());
+        }
+        _ => ENV.revert(br#"expected {"slots": ["0x...", ...], "complete": false}"#),
+    }
+    ENV.post_exec();
+}
+
+/// Replaces the contract code with the WebAssembly given as raw call data. Only the
+/// owner chosen at compile time may upgrade the contract. Storage is left untouched,
+/// so the new code must be compatible with the storage layout of the current one.
+#[no_mangle]
+pub unsafe fn _evm_upgrade(
+    owner_off: usize, // relative to _abi_buffer
+    owner_len: usize,
+) {
+    let owner_ptr: *mut u8 = _abi_buffer
+        .as_mut_ptr()
+        .offset(owner_off.try_into().unwrap());
+    let owner = std::slice::from_raw_parts(owner_ptr, owner_len);
+    if ENV.caller() != account_id_to_address::<Hasher>(owner) {
+        ENV.revert(b"only the owner may upgrade the contract");
+        ENV.post_exec();
+        return;
+    }
+    let code = ENV.call_data().to_vec();
+    if code.starts_with(b"\0asm") {
+        ENV.deploy_code(&code);
+        ENV.value_return(&[]);
+    } else {
+        ENV.revert(b"expected WebAssembly code");
+    }
+    ENV.post_exec();
+}
+
 #[no_mangle]
 pub unsafe fn _evm_pop_u32() -> u32 {
     EVM.stack.pop().as_u32()
@@ -1,5 +1,6 @@
-use crate::env::{Address, Env, EvmLog, ExitStatus};
+use crate::env::{Address, Env, EvmLog, ExitStatus, OwnedEvmLog};
 use crate::state::Word;
+use crate::storage::{self, Layout, RawStorage, StorageKey};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -8,9 +9,24 @@ pub struct MockEnv {
     pub address: Address,
     pub origin: Address,
     pub caller: Address,
+    /// The address registry, mapping addresses back to account IDs
+    pub accounts: Option<HashMap<Address, String>>,
+    pub call_value: Word,
+    pub balances: Option<HashMap<Address, Word>>,
+    /// Ledger of all value transfers, in order
+    pub transfers: Vec<(Address, Word)>,
     pub block_height: u64,
     pub timestamp: u64,
-    pub storage: Option<HashMap<Word, Word>>,
+    /// Hashes of recent blocks; any other block has a zero hash
+    pub block_hashes: Option<HashMap<u64, Word>>,
+    pub coinbase: Address,
+    pub prev_randao: Word,
+    pub base_fee: Word,
+    /// The raw key-value store, laid out like NEAR storage
+    pub storage: Option<HashMap<Vec<u8>, Vec<u8>>>,
+    pub created_in_transaction: bool,
+    /// The code deployed to replace the current contract, if any
+    pub deployed_code: Option<Vec<u8>>,
     pub logs: Vec<OwnedEvmLog>,
     pub return_data: Vec<u8>,
     pub exit_status: Option<ExitStatus>,
@@ -23,11 +39,44 @@ impl MockEnv {
         self.address = [0u8; 20];
         self.origin = [0u8; 20];
         self.caller = [0u8; 20];
+        self.accounts = None;
+        self.call_value = crate::state::ZERO;
+        self.balances = None;
+        self.transfers.clear();
         self.block_height = 0;
         self.timestamp = 0;
+        self.block_hashes = None;
+        self.coinbase = [0u8; 20];
+        self.prev_randao = crate::state::ZERO;
+        self.base_fee = crate::state::ZERO;
         self.storage = None;
+        self.created_in_transaction = false;
+        self.deployed_code = None;
         self.logs.clear();
     }
+
+    /// Records an account in the address registry, as if it had been seen
+    /// as the caller or origin, and returns its address.
+    #[cfg(test)]
+    pub fn register_account(&mut self, account_id: &str) -> Address {
+        let address = crate::registry::account_id_to_address::<crate::hash_provider::Native>(
+            account_id.as_bytes(),
+        );
+        if crate::registry::implicit_address(account_id.as_bytes()).is_none() {
+            self.accounts
+                .get_or_insert_with(HashMap::new)
+                .entry(address)
+                .or_insert_with(|| account_id.to_string());
+        }
+        address
+    }
+
+    #[cfg(test)]
+    pub fn set_balance(&mut self, address: Address, balance: Word) {
+        self.balances
+            .get_or_insert_with(HashMap::new)
+            .insert(address, balance);
+    }
 }
 
 impl Env for MockEnv {
@@ -51,6 +100,41 @@ impl Env for MockEnv {
         self.caller
     }
 
+    fn account_id(&mut self, address: Address) -> String {
+        self.accounts
+            .as_ref()
+            .and_then(|accounts| accounts.get(&address))
+            .cloned()
+            .unwrap_or_else(|| crate::registry::implicit_account_id(&address))
+    }
+
+    fn call_value(&self) -> Word {
+        self.call_value
+    }
+
+    fn balance(&mut self, address: Address) -> Word {
+        self.balances
+            .as_ref()
+            .and_then(|balances| balances.get(&address))
+            .copied()
+            .unwrap_or(crate::state::ZERO)
+    }
+
+    fn self_balance(&mut self) -> Word {
+        self.balance(self.address)
+    }
+
+    fn transfer(&mut self, to: Address, amount: Word) {
+        let from = self.address;
+        let balances = self.balances.get_or_insert_with(HashMap::new);
+        let from_balance = balances.entry(from).or_default();
+        *from_balance = from_balance
+            .checked_sub(amount)
+            .expect("transfer amount exceeds the balance");
+        *balances.entry(to).or_default() += amount;
+        self.transfers.push((to, amount));
+    }
+
     fn block_height(&self) -> u64 {
         self.block_height
     }
@@ -59,25 +143,50 @@ impl Env for MockEnv {
         self.timestamp
     }
 
-    fn storage_read(&mut self, key: Word) -> Word {
-        if self.storage.is_none() {
-            self.storage = Some(HashMap::new());
-        }
-
-        self.storage
+    fn block_hash(&mut self, number: u64) -> Word {
+        self.block_hashes
             .as_ref()
-            .unwrap()
-            .get(&key)
+            .and_then(|block_hashes| block_hashes.get(&number))
             .copied()
             .unwrap_or(crate::state::ZERO)
     }
 
+    fn coinbase(&self) -> Address {
+        self.coinbase
+    }
+
+    fn prev_randao(&mut self) -> Word {
+        self.prev_randao
+    }
+
+    fn base_fee(&self) -> Word {
+        self.base_fee
+    }
+
+    fn storage_read(&mut self, key: Word) -> Word {
+        Layout::load(self).read_slot(self, key)
+    }
+
     fn storage_write(&mut self, key: Word, value: Word) {
-        if self.storage.is_none() {
-            self.storage = Some(HashMap::new());
+        Layout::load(self).write_slot(self, key, value)
+    }
+
+    fn migrate_storage(&mut self, slots: &[Word], complete: bool) -> usize {
+        storage::migrate(self, slots, complete)
+    }
+
+    fn created_in_transaction(&self) -> bool {
+        self.created_in_transaction
+    }
+
+    fn clear_storage(&mut self) {
+        if let Some(storage) = self.storage.as_mut() {
+            storage.retain(|key, _| !StorageKey::is_slot(key));
         }
+    }
 
-        self.storage.as_mut().unwrap().insert(key, value);
+    fn deploy_code(&mut self, code: &[u8]) {
+        self.deployed_code = Some(code.to_vec());
     }
 
     fn log(&mut self, entry: EvmLog) {
@@ -100,7 +209,7 @@ impl Env for MockEnv {
         self.exit_status = Some(ExitStatus::OutOfGas);
     }
 
-    fn post_exec(&self) {
+    fn post_exec(&mut self) {
         match &self.exit_status {
             Some(status) => {
                 println!(
@@ -133,19 +242,20 @@ impl Env for MockEnv {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct OwnedEvmLog {
-    pub address: Address,
-    pub topics: Vec<Word>,
-    pub data: Vec<u8>,
-}
+impl RawStorage for MockEnv {
+    fn raw_read(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.storage.as_ref()?.get(key).cloned()
+    }
 
-impl<'a> From<EvmLog<'a>> for OwnedEvmLog {
-    fn from(log: EvmLog<'a>) -> Self {
-        Self {
-            address: log.address,
-            topics: log.topics.to_vec(),
-            data: log.data.to_vec(),
-        }
+    fn raw_write(&mut self, key: &[u8], value: &[u8]) {
+        self.storage
+            .get_or_insert_with(HashMap::new)
+            .insert(key.to_vec(), value.to_vec());
+    }
+
+    fn raw_remove(&mut self, key: &[u8]) -> bool {
+        self.storage
+            .as_mut()
+            .map_or(false, |storage| storage.remove(key).is_some())
     }
 }
@@ -16,10 +16,44 @@ pub trait Env {
     fn address(&mut self) -> Address;
     fn origin(&mut self) -> Address;
     fn caller(&mut self) -> Address;
+    /// The NEAR account behind the given address, as recorded in the address registry
+    /// when the account was last seen as the caller or origin. Falls back to the
+    /// Ethereum-compatible implicit account for addresses that were never seen.
+    fn account_id(&mut self, address: Address) -> String;
+    /// The value (in wei) attached to the current call
+    fn call_value(&self) -> Word;
+    /// The balance (in wei) of the given account
+    fn balance(&mut self, address: Address) -> Word;
+    /// The balance (in wei) of the current account, including the call value
+    fn self_balance(&mut self) -> Word;
+    /// Transfers value (in wei) from the current account to the given one.
+    /// The caller must ensure the amount does not exceed `self_balance`.
+    fn transfer(&mut self, to: Address, amount: Word);
     fn block_height(&self) -> u64;
     fn timestamp(&self) -> u64;
+    /// The hash of the given block, which must be one of the 256 blocks before
+    /// the current one. It must be unpredictable until that block is produced,
+    /// and the same whenever it is read afterwards; it may be zero for blocks
+    /// whose hash is unknown.
+    fn block_hash(&mut self, number: u64) -> Word;
+    /// The beneficiary of the current block's fees
+    fn coinbase(&self) -> Address;
+    /// The randomness provided by the current block (see EIP-4399)
+    fn prev_randao(&mut self) -> Word;
+    /// The base fee (in wei per unit of gas) of the current block
+    fn base_fee(&self) -> Word;
     fn storage_read(&mut self, key: Word) -> Word;
     fn storage_write(&mut self, key: Word, value: Word);
+    /// Moves the given slots to the latest storage layout (see `storage::migrate`)
+    fn migrate_storage(&mut self, slots: &[Word], complete: bool) -> usize;
+    /// Whether the current contract was created in the current transaction,
+    /// which is the only case where SELFDESTRUCT deletes it (see EIP-6780)
+    fn created_in_transaction(&self) -> bool;
+    /// Deletes all the storage of the current contract
+    fn clear_storage(&mut self);
+    /// Replaces the code of the current contract once the execution succeeds,
+    /// leaving its storage as is
+    fn deploy_code(&mut self, code: &[u8]);
     fn log(&mut self, entry: EvmLog);
     fn value_return(&mut self, return_data: &[u8]);
     fn revert(&mut self, return_data: &[u8]);
@@ -27,7 +61,7 @@ pub trait Env {
     fn exit_oog(&mut self);
     /// Called when all execution is finished to post the result
     /// to the parent runtime (eg NEAR runtime).
-    fn post_exec(&self);
+    fn post_exec(&mut self);
     fn get_return_data(&self) -> &[u8];
     fn get_exit_status(&self) -> &Option<ExitStatus>;
     /// Used to when encoding the output as JSON instead of raw bytes
@@ -67,6 +101,33 @@ impl<'a> EvmLog<'a> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedEvmLog {
+    pub address: Address,
+    pub topics: Vec<Word>,
+    pub data: Vec<u8>,
+}
+
+impl<'a> From<EvmLog<'a>> for OwnedEvmLog {
+    fn from(log: EvmLog<'a>) -> Self {
+        Self {
+            address: log.address,
+            topics: log.topics.to_vec(),
+            data: log.data.to_vec(),
+        }
+    }
+}
+
+impl OwnedEvmLog {
+    pub fn as_log(&self) -> EvmLog<'_> {
+        EvmLog {
+            address: self.address,
+            topics: &self.topics,
+            data: &self.data,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExitStatus {
     Success,
@@ -1,7 +1,22 @@
+use bn::{Fr, Gt, G1, G2};
+use k256::ecdsa::{recoverable, Signature};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use ripemd::Ripemd160;
+use sha2::Sha256;
 use sha3::{Digest, Keccak256};
 
 pub trait HashProvider {
     fn keccak256(input: &[u8]) -> [u8; 32];
+    fn sha256(input: &[u8]) -> [u8; 32];
+    fn ripemd160(input: &[u8]) -> [u8; 20];
+    /// Recovers the uncompressed public key (without the leading `0x04` byte)
+    /// that signed the given hash, or `None` if the signature is invalid.
+    /// High `s` values are accepted, as in the EVM.
+    fn ecrecover(hash: &[u8; 32], signature: &[u8; 64], recovery_id: u8) -> Option<[u8; 64]>;
+    fn bn254_add(a: G1, b: G1) -> G1;
+    fn bn254_mul(point: G1, scalar: Fr) -> G1;
+    /// Checks whether the product of the pairings of all the given pairs is one.
+    fn bn254_pairing_check(pairs: &[(G1, G2)]) -> bool;
 }
 
 pub struct Native;
@@ -11,4 +26,36 @@ impl HashProvider for Native {
         // Unwrap is safe because has is 256-bit
         Keccak256::digest(input).as_slice().try_into().unwrap()
     }
+
+    fn sha256(input: &[u8]) -> [u8; 32] {
+        Sha256::digest(input).into()
+    }
+
+    fn ripemd160(input: &[u8]) -> [u8; 20] {
+        Ripemd160::digest(input).into()
+    }
+
+    fn ecrecover(hash: &[u8; 32], signature: &[u8; 64], recovery_id: u8) -> Option<[u8; 64]> {
+        let signature = Signature::try_from(&signature[..]).ok()?;
+        let recovery_id = recoverable::Id::new(recovery_id).ok()?;
+        let signature = recoverable::Signature::new(&signature, recovery_id).ok()?;
+        let public_key = signature
+            .recover_verifying_key_from_digest_bytes(hash.into())
+            .ok()?;
+        let public_key = public_key.to_encoded_point(false);
+        // Unwrap is safe because an uncompressed point has 1 + 64 bytes
+        Some(public_key.as_bytes()[1..].try_into().unwrap())
+    }
+
+    fn bn254_add(a: G1, b: G1) -> G1 {
+        a + b
+    }
+
+    fn bn254_mul(point: G1, scalar: Fr) -> G1 {
+        point * scalar
+    }
+
+    fn bn254_pairing_check(pairs: &[(G1, G2)]) -> bool {
+        bn::pairing_batch(pairs) == Gt::one()
+    }
 }
@@ -0,0 +1,174 @@
+//! This module contains the journal of the changes made to the contract state during
+//! an execution: storage writes, logs and value transfers. These changes are only
+//! applied to the environment once the execution succeeds, so that reverting simply
+//! discards them, exactly like the EVM does. Checkpoints allow discarding only the
+//! changes made since, as needed when a nested call frame reverts.
+
+use crate::env::{Address, Env, OwnedEvmLog};
+use crate::state::{Word, ZERO};
+use std::collections::HashMap;
+
+pub(crate) struct Journal {
+    /// The latest value of every storage slot written
+    pub(crate) storage: Option<HashMap<Word, Word>>,
+    pub(crate) logs: Vec<OwnedEvmLog>,
+    pub(crate) transfers: Vec<(Address, Word)>,
+    /// The changes in order, so they can be undone
+    pub(crate) entries: Vec<JournalEntry>,
+}
+
+pub(crate) enum JournalEntry {
+    /// A storage write, with the previous value if the slot had already been written
+    Storage(Word, Option<Word>),
+    Log,
+    Transfer,
+}
+
+/// A point in the journal that can be rolled back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Checkpoint(usize);
+
+impl Journal {
+    pub fn storage_read(&self, key: Word) -> Option<Word> {
+        self.storage.as_ref()?.get(&key).copied()
+    }
+
+    pub fn storage_write(&mut self, key: Word, value: Word) {
+        let previous = self
+            .storage
+            .get_or_insert_with(HashMap::new)
+            .insert(key, value);
+        self.entries.push(JournalEntry::Storage(key, previous));
+    }
+
+    pub fn log(&mut self, log: OwnedEvmLog) {
+        self.logs.push(log);
+        self.entries.push(JournalEntry::Log);
+    }
+
+    pub fn transfer(&mut self, to: Address, amount: Word) {
+        self.transfers.push((to, amount));
+        self.entries.push(JournalEntry::Transfer);
+    }
+
+    /// The total value transferred to the given account
+    pub fn transferred_to(&self, address: &Address) -> Word {
+        self.transfers
+            .iter()
+            .filter(|(to, _)| to == address)
+            .fold(ZERO, |total, (_, amount)| total + amount)
+    }
+
+    /// The total value transferred from the current account
+    pub fn transferred(&self) -> Word {
+        self.transfers
+            .iter()
+            .fold(ZERO, |total, (_, amount)| total + amount)
+    }
+
+    #[allow(dead_code)] // only needed for nested calls
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.entries.len())
+    }
+
+    /// Undoes all the changes made since the given checkpoint.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        while self.entries.len() > checkpoint.0 {
+            match self.entries.pop().unwrap() {
+                JournalEntry::Storage(key, Some(previous)) => {
+                    self.storage.as_mut().unwrap().insert(key, previous);
+                }
+                JournalEntry::Storage(key, None) => {
+                    self.storage.as_mut().unwrap().remove(&key);
+                }
+                JournalEntry::Log => _ = self.logs.pop(),
+                JournalEntry::Transfer => _ = self.transfers.pop(),
+            }
+        }
+    }
+
+    /// Undoes all the changes.
+    pub fn discard(&mut self) {
+        self.rollback(Checkpoint(0))
+    }
+
+    /// Applies all the changes to the environment, and clears the journal.
+    pub fn commit<E: Env>(&mut self, env: &mut E) {
+        if let Some(storage) = self.storage.take() {
+            for (key, value) in storage {
+                env.storage_write(key, value);
+            }
+        }
+        for log in self.logs.drain(..) {
+            env.log(log.as_log());
+        }
+        for (to, amount) in self.transfers.drain(..) {
+            env.transfer(to, amount);
+        }
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::mock::MockEnv;
+
+    fn journal() -> Journal {
+        Journal {
+            storage: None,
+            logs: Vec::new(),
+            transfers: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut journal = journal();
+        let key = Word::from(7u8);
+        journal.storage_write(key, Word::from(1u8));
+        let checkpoint = journal.checkpoint();
+        journal.storage_write(key, Word::from(2u8));
+        journal.storage_write(Word::from(8u8), Word::from(3u8));
+        journal.transfer([1u8; 20], Word::from(4u8));
+        assert_eq!(journal.storage_read(key), Some(Word::from(2u8)));
+        assert_eq!(journal.transferred(), 4);
+        assert_eq!(journal.transferred_to(&[1u8; 20]), 4);
+
+        journal.rollback(checkpoint);
+        assert_eq!(journal.storage_read(key), Some(Word::from(1u8)));
+        assert_eq!(journal.storage_read(Word::from(8u8)), None);
+        assert_eq!(journal.transferred(), 0);
+
+        journal.discard();
+        assert_eq!(journal.storage_read(key), None);
+        assert!(journal.entries.is_empty());
+    }
+
+    #[test]
+    fn test_commit() {
+        let mut env = MockEnv::default();
+        let address = [0x9A; 20];
+        env.address = address;
+        env.set_balance(address, Word::from(10u8));
+
+        let mut journal = journal();
+        journal.storage_write(Word::from(7u8), Word::from(1u8));
+        journal.log(OwnedEvmLog {
+            address,
+            topics: Vec::new(),
+            data: vec![1, 2, 3],
+        });
+        journal.transfer([1u8; 20], Word::from(4u8));
+        assert_eq!(env.storage_read(Word::from(7u8)), 0);
+        assert!(env.logs.is_empty());
+
+        journal.commit(&mut env);
+        assert_eq!(env.storage_read(Word::from(7u8)), 1);
+        assert_eq!(env.logs.len(), 1);
+        assert_eq!(env.transfers, vec![([1u8; 20], Word::from(4u8))]);
+        assert!(journal.entries.is_empty());
+        assert_eq!(journal.storage_read(Word::from(7u8)), None);
+    }
+}
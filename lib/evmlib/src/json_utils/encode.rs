@@ -38,7 +38,7 @@ pub fn encode_return_data_as_json(
         ExitStatus::Revert => {
             json_result.insert("status".into(), serde_json::Value::String("REVERT".into()));
             // Check for standard Solidity error format
-            if return_data[0..4] == [0x08, 0xc3, 0x79, 0xa0] {
+            if return_data.starts_with(&[0x08, 0xc3, 0x79, 0xa0]) {
                 let mut return_tokens =
                     ethabi::decode(&[ethabi::ParamType::String], &return_data[4..])
                         .map_err(|_| EncodeReturnDataError::ReturnDataDecodeFailure)?;
@@ -131,4 +131,16 @@ mod tests {
         let expected_output = r#"{"output":42,"status":"SUCCESS"}"#.as_bytes();
         assert_eq!(&output, expected_output);
     }
+
+    #[test]
+    fn test_encode_empty_revert_as_json() {
+        let output = super::encode_return_data_as_json(
+            b"int256",
+            &[],
+            &Some(crate::env::ExitStatus::Revert),
+        )
+        .unwrap();
+        let expected_output = r#"{"error":"0x","status":"REVERT"}"#.as_bytes();
+        assert_eq!(&output, expected_output);
+    }
 }
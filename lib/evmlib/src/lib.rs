@@ -5,10 +5,14 @@
 mod api;
 mod env;
 mod hash_provider;
+mod journal;
 mod json_utils;
 mod near_runtime;
 mod ops;
+mod precompiles;
+mod registry;
 mod state;
+mod storage;
 
 #[cfg(test)]
 mod ops_test;
@@ -0,0 +1,56 @@
+//! Encoding of BN254 (alt_bn128) values for the NEAR host functions, which expect
+//! field elements in little-endian order, and the real part of `Fq2` elements first.
+//! The point at infinity is never passed to the host.
+
+use bn::{AffineG1, AffineG2, Fq, Fr, Group, G1, G2};
+
+pub const G1_SIZE: usize = 64;
+
+pub fn encode_g1(point: G1, output: &mut Vec<u8>) {
+    // Unwrap is safe because the caller never passes the point at infinity
+    let point = AffineG1::from_jacobian(point).unwrap();
+    encode_fq(point.x(), output);
+    encode_fq(point.y(), output);
+}
+
+pub fn encode_g2(point: G2, output: &mut Vec<u8>) {
+    // Unwrap is safe because the caller never passes the point at infinity
+    let point = AffineG2::from_jacobian(point).unwrap();
+    encode_fq(point.x().real(), output);
+    encode_fq(point.x().imaginary(), output);
+    encode_fq(point.y().real(), output);
+    encode_fq(point.y().imaginary(), output);
+}
+
+pub fn encode_fr(scalar: Fr, output: &mut Vec<u8>) {
+    let mut bytes = [0u8; 32];
+    // Unwrap is safe because the buffer has the right length
+    scalar.to_big_endian(&mut bytes).unwrap();
+    bytes.reverse();
+    output.extend_from_slice(&bytes);
+}
+
+pub fn decode_g1(bytes: &[u8; G1_SIZE]) -> G1 {
+    if bytes.iter().all(|b| *b == 0) {
+        return G1::zero();
+    }
+    let x = decode_fq(&bytes[0..32]);
+    let y = decode_fq(&bytes[32..64]);
+    // Unwrap is safe because the host only returns valid points
+    AffineG1::new(x, y).unwrap().into()
+}
+
+fn encode_fq(value: Fq, output: &mut Vec<u8>) {
+    let mut bytes = [0u8; 32];
+    // Unwrap is safe because the buffer has the right length
+    value.to_big_endian(&mut bytes).unwrap();
+    bytes.reverse();
+    output.extend_from_slice(&bytes);
+}
+
+fn decode_fq(bytes: &[u8]) -> Fq {
+    let mut bytes: [u8; 32] = bytes.try_into().unwrap();
+    bytes.reverse();
+    // Unwrap is safe because the host only returns valid field elements
+    Fq::from_slice(&bytes).unwrap()
+}
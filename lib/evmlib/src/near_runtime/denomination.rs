@@ -0,0 +1,65 @@
+use crate::state::Word;
+
+/// NEAR has 24 decimal places, so a larger scale would round every amount to zero.
+pub const MAX_SCALE: u8 = 24;
+
+/// Converts between NEAR token amounts (in yoctoNEAR) and EVM values (in wei).
+///
+/// The scale is the number of decimal places dropped when converting yoctoNEAR
+/// to wei. The default scale of 0 maps one yoctoNEAR to one wei, while a scale
+/// of 6 maps one NEAR to one ether.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Denomination {
+    scale: u8,
+}
+
+impl Denomination {
+    pub const fn new(scale: u8) -> Self {
+        assert!(scale <= MAX_SCALE, "denomination scale out of range");
+        Self { scale }
+    }
+
+    fn yocto_per_wei(&self) -> u128 {
+        10u128.pow(self.scale as u32)
+    }
+
+    /// Converts yoctoNEAR to wei, truncating any amount smaller than one wei.
+    pub fn to_wei(&self, yocto: u128) -> Word {
+        Word::from(yocto / self.yocto_per_wei())
+    }
+
+    /// Converts wei to yoctoNEAR, or `None` if the result exceeds `u128::MAX`.
+    pub fn to_yocto(&self, wei: Word) -> Option<u128> {
+        u128::try_from(wei).ok()?.checked_mul(self.yocto_per_wei())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_wei() {
+        assert_eq!(Denomination::default().to_wei(1234), 1234);
+        assert_eq!(Denomination::new(6).to_wei(10u128.pow(24)), 10u128.pow(18));
+        assert_eq!(Denomination::new(6).to_wei(999_999), 0);
+        assert_eq!(
+            Denomination::new(MAX_SCALE).to_wei(u128::MAX),
+            340282366920938
+        );
+    }
+
+    #[test]
+    fn test_to_yocto() {
+        assert_eq!(
+            Denomination::default().to_yocto(Word::from(1234u32)),
+            Some(1234)
+        );
+        assert_eq!(
+            Denomination::new(6).to_yocto(Word::from(10u128.pow(18))),
+            Some(10u128.pow(24))
+        );
+        assert_eq!(Denomination::new(6).to_yocto(Word::from(u128::MAX)), None);
+        assert_eq!(Denomination::default().to_yocto(Word::MAX), None);
+    }
+}
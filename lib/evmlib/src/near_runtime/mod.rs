@@ -3,11 +3,18 @@
 
 use crate::env::{Address, Env, ExitStatus};
 use crate::hash_provider::HashProvider;
+use crate::registry;
 use crate::state::Word;
+use crate::storage::{self, Layout, RawStorage, StorageKey};
+use bn::{Fr, Group, G1, G2};
 use std::collections::HashMap;
 
-mod storage;
+mod alt_bn128;
+mod denomination;
 
+pub use denomination::Denomination;
+
+// Used by all HashProvider functions, which read the result right away.
 const KECCAK_REGISTER_ID: u64 = 1;
 // This register can be safely used for all Env functions that get account_ids from the host
 // because we always use the data before returning from the function, so it does not
@@ -16,16 +23,37 @@ const ACCOUNT_REGISTER_ID: u64 = 2;
 // The input must have its own register because we only set it once (as an optimization).
 const INPUT_REGISTER_ID: u64 = 3;
 const STORAGE_REGISTER_ID: u64 = 4;
+const RANDOM_SEED_REGISTER_ID: u64 = 5;
+
+/// The minimum gas price of the NEAR protocol, in yoctoNEAR per unit of gas.
+const MIN_GAS_PRICE: u128 = 100_000_000;
 
 pub struct NearRuntime {
     /// Cache for input from NEAR to prevent reading from the register multiple times.
     pub call_data: Option<Vec<u8>>,
-    pub storage_cache: Option<HashMap<Word, Word>>,
+    pub storage_cache: Option<HashMap<Word, CachedSlot>>,
+    pub storage_layout: Option<Layout>,
     pub address_cache: Option<Address>,
     pub origin_cache: Option<Address>,
     pub caller_cache: Option<Address>,
+    pub random_seed_cache: Option<[u8; 32]>,
     pub exit_status: Option<ExitStatus>,
     pub return_data: Vec<u8>,
+    /// Conversion between NEAR amounts and EVM values, configured by the compiler.
+    pub denomination: Denomination,
+}
+
+/// A storage slot that was read or written during the execution.
+pub struct CachedSlot {
+    /// The value in storage, if it was read before being written
+    original: Option<Word>,
+    value: Word,
+}
+
+impl CachedSlot {
+    fn is_dirty(&self) -> bool {
+        self.original != Some(self.value)
+    }
 }
 
 impl HashProvider for NearRuntime {
@@ -41,6 +69,111 @@ impl HashProvider for NearRuntime {
         Self::read_register_to_buffer(KECCAK_REGISTER_ID, &mut host_result);
         host_result
     }
+
+    fn sha256(input: &[u8]) -> [u8; 32] {
+        unsafe {
+            sha256(
+                input.len() as u64,
+                input.as_ptr() as u64,
+                KECCAK_REGISTER_ID,
+            );
+        }
+        let mut host_result = [0u8; 32];
+        Self::read_register_to_buffer(KECCAK_REGISTER_ID, &mut host_result);
+        host_result
+    }
+
+    fn ripemd160(input: &[u8]) -> [u8; 20] {
+        unsafe {
+            ripemd160(
+                input.len() as u64,
+                input.as_ptr() as u64,
+                KECCAK_REGISTER_ID,
+            );
+        }
+        let mut host_result = [0u8; 20];
+        Self::read_register_to_buffer(KECCAK_REGISTER_ID, &mut host_result);
+        host_result
+    }
+
+    fn ecrecover(hash: &[u8; 32], signature: &[u8; 64], recovery_id: u8) -> Option<[u8; 64]> {
+        let host_result = unsafe {
+            ecrecover(
+                hash.len() as u64,
+                hash.as_ptr() as u64,
+                signature.len() as u64,
+                signature.as_ptr() as u64,
+                recovery_id.into(),
+                0, // the EVM does not check for malleability
+                KECCAK_REGISTER_ID,
+            )
+        };
+        if host_result == 0 {
+            return None;
+        }
+        let mut public_key = [0u8; 64];
+        Self::read_register_to_buffer(KECCAK_REGISTER_ID, &mut public_key);
+        Some(public_key)
+    }
+
+    fn bn254_add(a: G1, b: G1) -> G1 {
+        if a.is_zero() {
+            return b;
+        }
+        if b.is_zero() {
+            return a;
+        }
+        let mut input = Vec::with_capacity(2 * (1 + alt_bn128::G1_SIZE));
+        for point in [a, b] {
+            input.push(0); // positive sign
+            alt_bn128::encode_g1(point, &mut input);
+        }
+        unsafe {
+            alt_bn128_g1_sum(
+                input.len() as u64,
+                input.as_ptr() as u64,
+                KECCAK_REGISTER_ID,
+            );
+        }
+        let mut host_result = [0u8; alt_bn128::G1_SIZE];
+        Self::read_register_to_buffer(KECCAK_REGISTER_ID, &mut host_result);
+        alt_bn128::decode_g1(&host_result)
+    }
+
+    fn bn254_mul(point: G1, scalar: Fr) -> G1 {
+        if point.is_zero() || scalar.is_zero() {
+            return G1::zero();
+        }
+        let mut input = Vec::with_capacity(alt_bn128::G1_SIZE + 32);
+        alt_bn128::encode_g1(point, &mut input);
+        alt_bn128::encode_fr(scalar, &mut input);
+        unsafe {
+            alt_bn128_g1_multiexp(
+                input.len() as u64,
+                input.as_ptr() as u64,
+                KECCAK_REGISTER_ID,
+            );
+        }
+        let mut host_result = [0u8; alt_bn128::G1_SIZE];
+        Self::read_register_to_buffer(KECCAK_REGISTER_ID, &mut host_result);
+        alt_bn128::decode_g1(&host_result)
+    }
+
+    fn bn254_pairing_check(pairs: &[(G1, G2)]) -> bool {
+        let mut input = Vec::new();
+        // Pairs involving the point at infinity do not affect the product
+        for (g1, g2) in pairs
+            .iter()
+            .filter(|(g1, g2)| !g1.is_zero() && !g2.is_zero())
+        {
+            alt_bn128::encode_g1(*g1, &mut input);
+            alt_bn128::encode_g2(*g2, &mut input);
+        }
+        if input.is_empty() {
+            return true;
+        }
+        unsafe { alt_bn128_pairing_check(input.len() as u64, input.as_ptr() as u64) == 1 }
+    }
 }
 
 impl Env for NearRuntime {
@@ -82,7 +215,7 @@ impl Env for NearRuntime {
 
         let address = unsafe {
             current_account_id(ACCOUNT_REGISTER_ID);
-            Self::account_id_to_address()
+            Self::account_id_to_address(false)
         };
 
         self.address_cache = Some(address);
@@ -96,7 +229,7 @@ impl Env for NearRuntime {
 
         let address = unsafe {
             signer_account_id(ACCOUNT_REGISTER_ID);
-            Self::account_id_to_address()
+            Self::account_id_to_address(true)
         };
 
         self.origin_cache = Some(address);
@@ -110,13 +243,57 @@ impl Env for NearRuntime {
 
         let address = unsafe {
             predecessor_account_id(ACCOUNT_REGISTER_ID);
-            Self::account_id_to_address()
+            Self::account_id_to_address(true)
         };
 
         self.caller_cache = Some(address);
         address
     }
 
+    fn account_id(&mut self, address: Address) -> String {
+        let storage_key = StorageKey::from_address(&address);
+        match Self::inner_storage_read(storage_key.as_slice()) {
+            Some(account_id) => String::from_utf8(account_id).expect("invalid account ID"),
+            None => registry::implicit_account_id(&address),
+        }
+    }
+
+    fn call_value(&self) -> Word {
+        let mut deposit = [0u8; 16];
+        unsafe { attached_deposit(deposit.as_mut_ptr() as u64) };
+        self.denomination.to_wei(u128::from_le_bytes(deposit))
+    }
+
+    fn balance(&mut self, address: Address) -> Word {
+        // NEAR has no synchronous way to query the balance of another account,
+        // so we only know about our own.
+        if address == self.address() {
+            self.self_balance()
+        } else {
+            Word::ZERO
+        }
+    }
+
+    fn self_balance(&mut self) -> Word {
+        let mut balance = [0u8; 16];
+        unsafe { account_balance(balance.as_mut_ptr() as u64) };
+        self.denomination.to_wei(u128::from_le_bytes(balance))
+    }
+
+    fn transfer(&mut self, to: Address, amount: Word) {
+        let account_id = self.account_id(to);
+        let amount = self
+            .denomination
+            .to_yocto(amount)
+            .expect("transfer amount exceeds the balance")
+            .to_le_bytes();
+        unsafe {
+            let promise_index =
+                promise_batch_create(account_id.len() as u64, account_id.as_ptr() as u64);
+            promise_batch_action_transfer(promise_index, amount.as_ptr() as u64);
+        }
+    }
+
     fn block_height(&self) -> u64 {
         unsafe { block_index() }
     }
@@ -127,43 +304,109 @@ impl Env for NearRuntime {
         ns / 1_000_000_000
     }
 
-    fn storage_read(&mut self, key: Word) -> Word {
-        if self.storage_cache.is_none() {
-            self.storage_cache = Some(HashMap::new());
-        }
-        let storage_cache = self.storage_cache.as_mut().unwrap();
+    /// NEAR does not give contracts access to past block hashes, so this derives
+    /// a hash from the block number and the random seed that the contract recorded
+    /// in that block, which it does whenever a call changes its storage (see
+    /// `flush_storage`). Blocks without such a call have a zero hash.
+    fn block_hash(&mut self, number: u64) -> Word {
+        let seed = match storage::block_seed(self, number) {
+            Some(seed) => seed,
+            None => return Word::ZERO,
+        };
+        let mut input = [0u8; 32 + 8];
+        input[..32].copy_from_slice(&seed);
+        input[32..].copy_from_slice(&number.to_be_bytes());
+        Word::from_be_bytes(Self::keccak256(&input))
+    }
+
+    /// NEAR does not expose the block producer to contracts, so this is always
+    /// the zero address.
+    fn coinbase(&self) -> Address {
+        [0u8; 20]
+    }
+
+    /// The random seed of the current block, which is shared by all
+    /// receipts in the same block.
+    fn prev_randao(&mut self) -> Word {
+        Word::from_be_bytes(self.random_seed())
+    }
 
-        if let Some(value) = storage_cache.get(&key) {
-            return *value;
+    /// NEAR does not expose the current gas price to contracts, so this is the
+    /// protocol's minimum gas price. Note that NEAR gas is not comparable to EVM gas.
+    fn base_fee(&self) -> Word {
+        self.denomination.to_wei(MIN_GAS_PRICE)
+    }
+
+    fn storage_read(&mut self, key: Word) -> Word {
+        if let Some(slot) = self
+            .storage_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&key))
+        {
+            return slot.value;
         }
 
-        let storage_key = storage::StorageKey::from_word(key);
-        let host_result = Self::inner_storage_read(storage_key.as_slice());
-        let value = host_result
-            .map(|bytes| Word::from_be_bytes(bytes.try_into().unwrap()))
-            .unwrap_or(Word::ZERO);
-        storage_cache.insert(key, value);
+        let value = self.storage_layout().read_slot(self, key);
+        let slot = CachedSlot {
+            original: Some(value),
+            value,
+        };
+        self.storage_cache
+            .get_or_insert_with(HashMap::new)
+            .insert(key, slot);
         value
     }
 
+    /// Writes are only cached here, and are flushed to storage by `post_exec`
+    /// if the execution succeeds.
     fn storage_write(&mut self, key: Word, value: Word) {
-        if self.storage_cache.is_none() {
-            self.storage_cache = Some(HashMap::new());
-        }
-        // Unwrap is safe because we ensure it is Some(..) in the check above
-        let previous_value = self.storage_cache.as_mut().unwrap().insert(key, value);
-        // Storage needs to be updated if we have never seen this key/value pair before,
-        // or if the value is different what from what it used to be.
-        let need_to_update_storage = match previous_value {
-            None => true,
-            Some(x) if x != value => true,
-            _ => false,
-        };
+        self.storage_cache
+            .get_or_insert_with(HashMap::new)
+            .entry(key)
+            .and_modify(|slot| slot.value = value)
+            .or_insert(CachedSlot {
+                original: None,
+                value,
+            });
+    }
+
+    fn migrate_storage(&mut self, slots: &[Word], complete: bool) -> usize {
+        let moved = storage::migrate(self, slots, complete);
+        self.storage_layout = None;
+        moved
+    }
+
+    /// Contracts are deployed to NEAR accounts as WebAssembly, and the EVM constructor
+    /// never runs on-chain, so a contract is never created by the current transaction.
+    fn created_in_transaction(&self) -> bool {
+        false
+    }
+
+    /// NEAR contracts cannot enumerate their storage, but a contract created in the
+    /// current transaction can only have written the slots in the storage cache.
+    fn clear_storage(&mut self) {
+        if let Some(storage_cache) = self.storage_cache.as_mut() {
+            for slot in storage_cache.values_mut() {
+                slot.value = Word::ZERO;
+            }
+        }
+    }
 
-        if need_to_update_storage {
-            let storage_key = storage::StorageKey::from_word(key);
-            let storage_value = value.to_be_bytes();
-            Self::inner_storage_write(storage_key.as_slice(), &storage_value);
+    /// Deploying to the current account only takes effect after the receipt succeeds,
+    /// and NEAR keeps the account's storage across deployments.
+    fn deploy_code(&mut self, code: &[u8]) {
+        let account_id = unsafe {
+            current_account_id(ACCOUNT_REGISTER_ID);
+            Self::read_register(ACCOUNT_REGISTER_ID)
+        };
+        unsafe {
+            let promise_index =
+                promise_batch_create(account_id.len() as u64, account_id.as_ptr() as u64);
+            promise_batch_action_deploy_contract(
+                promise_index,
+                code.len() as u64,
+                code.as_ptr() as u64,
+            );
         }
     }
 
@@ -188,9 +431,10 @@ impl Env for NearRuntime {
         self.exit_status = Some(ExitStatus::OutOfGas);
     }
 
-    fn post_exec(&self) {
-        match &self.exit_status {
+    fn post_exec(&mut self) {
+        match self.exit_status {
             Some(ExitStatus::Success) => {
+                self.flush_storage();
                 let return_data = &self.return_data;
                 unsafe {
                     value_return(return_data.len() as u64, return_data.as_ptr() as u64);
@@ -255,6 +499,50 @@ impl NearRuntime {
         }
     }
 
+    fn random_seed(&mut self) -> [u8; 32] {
+        if let Some(random_seed) = self.random_seed_cache {
+            return random_seed;
+        }
+
+        unsafe { random_seed(RANDOM_SEED_REGISTER_ID) };
+        let mut seed = [0u8; 32];
+        Self::read_register_to_buffer(RANDOM_SEED_REGISTER_ID, &mut seed);
+        self.random_seed_cache = Some(seed);
+        seed
+    }
+
+    /// Writes the slots that changed during the execution to storage,
+    /// deleting the ones that were set to zero (see `Layout::write_slot`).
+    /// Calls that change storage also record the random seed of the current
+    /// block, for `block_hash`; other calls may be view calls, which cannot.
+    fn flush_storage(&mut self) {
+        let storage_cache = match self.storage_cache.take() {
+            Some(storage_cache) => storage_cache,
+            None => return,
+        };
+        let layout = self.storage_layout();
+        let mut changed = false;
+        for (key, slot) in storage_cache.iter().filter(|(_, slot)| slot.is_dirty()) {
+            layout.write_slot(self, *key, slot.value);
+            changed = true;
+        }
+        self.storage_cache = Some(storage_cache);
+        if changed {
+            let seed = self.random_seed();
+            storage::record_block_seed(self, self.block_height(), &seed);
+        }
+    }
+
+    fn storage_layout(&mut self) -> Layout {
+        if let Some(layout) = self.storage_layout {
+            return layout;
+        }
+
+        let layout = Layout::load(self);
+        self.storage_layout = Some(layout);
+        layout
+    }
+
     fn inner_storage_read(key: &[u8]) -> Option<Vec<u8>> {
         let host_result =
             unsafe { storage_read(key.len() as u64, key.as_ptr() as u64, STORAGE_REGISTER_ID) };
@@ -278,20 +566,51 @@ impl NearRuntime {
         }
     }
 
-    /// This function uses the data in `ACCOUNT_REGISTER_ID` as the input
-    /// to the hash function which is used to derive the address. It is marked as
-    /// unsafe to flag that register must be properly set before calling this function.
-    unsafe fn account_id_to_address() -> Address {
-        unsafe {
-            // We can pass one register's value as the input to another host function
-            // by specifying a length of u64::MAX. See https://nomicon.io/Proposals/bindings#registers
-            keccak256(u64::MAX, ACCOUNT_REGISTER_ID, KECCAK_REGISTER_ID);
+    fn inner_storage_remove(key: &[u8]) -> bool {
+        let host_result =
+            unsafe { storage_remove(key.len() as u64, key.as_ptr() as u64, STORAGE_REGISTER_ID) };
+        host_result == 1
+    }
+
+    /// This function uses the account ID in `ACCOUNT_REGISTER_ID` to derive the address,
+    /// recording it in the address registry if asked to. It is marked as unsafe to flag
+    /// that register must be properly set before calling this function.
+    unsafe fn account_id_to_address(register: bool) -> Address {
+        let account_id = Self::read_register(ACCOUNT_REGISTER_ID);
+        let address = registry::account_id_to_address::<Self>(&account_id);
+        if register {
+            Self::register_account(&address, &account_id);
+        }
+        address
+    }
+
+    /// Records the account ID of an address on first sight, unless the address
+    /// can be mapped back without the registry. This writes to storage, so it
+    /// must only be used where view calls are prohibited anyway.
+    fn register_account(address: &Address, account_id: &[u8]) {
+        if registry::implicit_address(account_id).is_some() {
+            return;
+        }
+        let storage_key = StorageKey::from_address(address);
+        let key = storage_key.as_slice();
+        let registered = unsafe { storage_has_key(key.len() as u64, key.as_ptr() as u64) };
+        if registered == 0 {
+            Self::inner_storage_write(key, account_id);
         }
-        let mut hash = [0u8; 32];
-        Self::read_register_to_buffer(KECCAK_REGISTER_ID, &mut hash);
-        let mut result = [0u8; 20];
-        result.copy_from_slice(&hash[12..32]);
-        result
+    }
+}
+
+impl RawStorage for NearRuntime {
+    fn raw_read(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        Self::inner_storage_read(key)
+    }
+
+    fn raw_write(&mut self, key: &[u8], value: &[u8]) {
+        Self::inner_storage_write(key, value)
+    }
+
+    fn raw_remove(&mut self, key: &[u8]) -> bool {
+        Self::inner_storage_remove(key)
     }
 }
 
@@ -302,10 +621,27 @@ extern "C" {
     fn current_account_id(register_id: u64);
     fn signer_account_id(register_id: u64);
     fn predecessor_account_id(register_id: u64);
+    fn attached_deposit(balance_ptr: u64);
+    fn account_balance(balance_ptr: u64);
     fn block_index() -> u64;
     fn block_timestamp() -> u64;
+    fn random_seed(register_id: u64);
     fn input(register_id: u64);
     fn keccak256(value_len: u64, value_ptr: u64, register_id: u64);
+    fn sha256(value_len: u64, value_ptr: u64, register_id: u64);
+    fn ripemd160(value_len: u64, value_ptr: u64, register_id: u64);
+    fn ecrecover(
+        hash_len: u64,
+        hash_ptr: u64,
+        sig_len: u64,
+        sig_ptr: u64,
+        v: u64,
+        malleability_flag: u64,
+        register_id: u64,
+    ) -> u64;
+    fn alt_bn128_g1_multiexp(value_len: u64, value_ptr: u64, register_id: u64);
+    fn alt_bn128_g1_sum(value_len: u64, value_ptr: u64, register_id: u64);
+    fn alt_bn128_pairing_check(value_len: u64, value_ptr: u64) -> u64;
 
     fn storage_write(
         key_len: u64,
@@ -315,9 +651,15 @@ extern "C" {
         register_id: u64,
     ) -> u64;
     fn storage_read(key_len: u64, key_ptr: u64, register_id: u64) -> u64;
+    fn storage_remove(key_len: u64, key_ptr: u64, register_id: u64) -> u64;
+    fn storage_has_key(key_len: u64, key_ptr: u64) -> u64;
 
     fn log_utf8(len: u64, ptr: u64);
 
+    fn promise_batch_create(account_id_len: u64, account_id_ptr: u64) -> u64;
+    fn promise_batch_action_transfer(promise_index: u64, amount_ptr: u64);
+    fn promise_batch_action_deploy_contract(promise_index: u64, code_len: u64, code_ptr: u64);
+
     fn value_return(value_len: u64, value_ptr: u64);
     fn panic_utf8(len: u64, ptr: u64) -> !;
 }
@@ -10,6 +10,8 @@ use ux::*;
 use crate::{
     env::{Address, Env, EvmLog},
     hash_provider::HashProvider,
+    journal::Journal,
+    precompiles::Precompile,
     state::{Machine, Memory, Stack, Word, MAX_STACK_DEPTH, ONE, ZERO},
 };
 
@@ -34,18 +36,28 @@ pub(crate) static mut EVM: Machine = Machine {
     call_value: Word::ZERO,
     code: Vec::new(),
     chain_id: ZERO,
-    self_balance: ZERO,
+    return_data: Vec::new(),
+    transient_storage: None,
+    journal: Journal {
+        storage: None,
+        logs: Vec::new(),
+        transfers: Vec::new(),
+        entries: Vec::new(),
+    },
 };
 
 #[cfg(all(feature = "near", not(test)))]
 pub(crate) static mut ENV: crate::near_runtime::NearRuntime = crate::near_runtime::NearRuntime {
     call_data: None,
     storage_cache: None,
+    storage_layout: None,
     address_cache: None,
     origin_cache: None,
     caller_cache: None,
+    random_seed_cache: None,
     exit_status: None,
     return_data: Vec::new(),
+    denomination: crate::near_runtime::Denomination::new(0),
 };
 
 #[cfg(any(not(feature = "near"), test))]
@@ -54,9 +66,19 @@ pub(crate) static mut ENV: crate::env::mock::MockEnv = crate::env::mock::MockEnv
     address: [0u8; 20],
     origin: [0u8; 20],
     caller: [0u8; 20],
+    accounts: None,
+    call_value: ZERO,
+    balances: None,
+    transfers: Vec::new(),
     block_height: 0,
     timestamp: 0,
+    block_hashes: None,
+    coinbase: [0u8; 20],
+    prev_randao: ZERO,
+    base_fee: ZERO,
     storage: None,
+    created_in_transaction: false,
+    deployed_code: None,
     logs: Vec::new(),
     return_data: Vec::new(),
     exit_status: None,
@@ -91,6 +113,7 @@ macro_rules! trace {
 pub unsafe fn stop() {
     EVM.burn_gas(0);
     EVM.stack.clear();
+    EVM.journal.commit(&mut ENV);
     ENV.value_return(&[]);
     trace!("STOP");
 }
@@ -399,12 +422,7 @@ pub unsafe fn balance() {
     EVM.burn_gas(100);
     let address_u256 = EVM.stack.pop();
     let address = u256_to_address(address_u256);
-    let result = if address == ENV.address() {
-        EVM.self_balance
-    } else {
-        ZERO
-    };
-    EVM.stack.push(result);
+    EVM.stack.push(current_balance(address));
     trace!("BALANCE address={}", address_u256);
 }
 
@@ -540,12 +558,7 @@ pub unsafe fn extcodecopy() {
 #[no_mangle]
 pub unsafe fn returndatasize() {
     EVM.burn_gas(2);
-    // Without any implementation of `CALL` there can be no sub-context
-    // to have produced return data used in a larger execution.
-    // We could consider using NEAR's promise API as the previous return data,
-    // but that may not be what we want depending on the design of `CALL`.
-    // For now we will simply always return `ZERO`.
-    EVM.stack.push(ZERO);
+    EVM.stack.push(Word::from(EVM.return_data.len() as u64));
     trace!("RETURNDATASIZE");
 }
 
@@ -553,8 +566,9 @@ pub unsafe fn returndatasize() {
 pub unsafe fn returndatacopy() {
     EVM.burn_gas(3);
     let (dest_offset, offset, size) = EVM.stack.pop3();
-    // See note in `returndatasize` about why we assume the return data is always empty.
-    data_copy(dest_offset, offset, size, &[]);
+    let return_data = std::mem::take(&mut EVM.return_data);
+    data_copy(dest_offset, offset, size, &return_data);
+    EVM.return_data = return_data;
     trace!(
         "RETURNDATACOPY dest_offset={} offset={} size={}",
         dest_offset,
@@ -582,14 +596,22 @@ pub unsafe fn extcodehash() {
 #[no_mangle]
 pub unsafe fn blockhash() {
     EVM.burn_gas(20);
-    EVM.stack.push(ZERO); // TODO: NEAR SDK
-    trace!("BLOCKHASH");
+    let number = EVM.stack.pop();
+    let current = ENV.block_height();
+    // Only the 256 most recent blocks are available, excluding the current one
+    let hash = match u64::try_from(number) {
+        Ok(number) if number < current && current - number <= 256 => ENV.block_hash(number),
+        _ => ZERO,
+    };
+    EVM.stack.push(hash);
+    trace!("BLOCKHASH number={}", number);
 }
 
 #[no_mangle]
 pub unsafe fn coinbase() {
     EVM.burn_gas(2);
-    EVM.stack.push(ZERO); // TODO: NEAR SDK
+    let address = ENV.coinbase();
+    EVM.stack.push(address_to_u256(&address));
     trace!("COINBASE");
 }
 
@@ -611,9 +633,10 @@ pub unsafe fn number() {
 
 #[no_mangle]
 pub unsafe fn difficulty() {
+    // Since the merge, this opcode is PREVRANDAO (see EIP-4399)
     EVM.burn_gas(2);
-    EVM.stack.push(ZERO);
-    trace!("DIFFICULTY");
+    EVM.stack.push(ENV.prev_randao());
+    trace!("PREVRANDAO");
 }
 
 #[no_mangle]
@@ -633,17 +656,34 @@ pub unsafe fn chainid() {
 #[no_mangle]
 pub unsafe fn selfbalance() {
     EVM.burn_gas(5);
-    EVM.stack.push(EVM.self_balance);
+    EVM.stack.push(current_self_balance());
     trace!("SELFBALANCE");
 }
 
 #[no_mangle]
 pub unsafe fn basefee() {
     EVM.burn_gas(2);
-    EVM.stack.push(ZERO);
+    EVM.stack.push(ENV.base_fee());
     trace!("BASEFEE");
 }
 
+#[no_mangle]
+pub unsafe fn blobhash() {
+    // NEAR transactions never carry blobs (see EIP-4844)
+    EVM.burn_gas(3);
+    let _index = EVM.stack.pop();
+    EVM.stack.push(ZERO);
+    trace!("BLOBHASH index={}", _index);
+}
+
+#[no_mangle]
+pub unsafe fn blobbasefee() {
+    // Without any blobs, the blob base fee stays at its minimum (see EIP-7516)
+    EVM.burn_gas(2);
+    EVM.stack.push(ONE);
+    trace!("BLOBBASEFEE");
+}
+
 #[no_mangle]
 pub unsafe fn pop() {
     EVM.burn_gas(2);
@@ -685,7 +725,10 @@ pub unsafe fn sload() {
     EVM.burn_gas(100);
     // TODO: dynamic hot/cold gas cost
     let key = EVM.stack.pop();
-    let value = ENV.storage_read(key);
+    let value = EVM
+        .journal
+        .storage_read(key)
+        .unwrap_or_else(|| ENV.storage_read(key));
     EVM.stack.push(value);
     trace!("SLOAD key={}", key);
 }
@@ -695,7 +738,7 @@ pub unsafe fn sstore() {
     EVM.burn_gas(100);
     // TODO: dynamic hot/cold gas cost
     let (key, value) = EVM.stack.pop2();
-    ENV.storage_write(key, value);
+    EVM.journal.storage_write(key, value);
     trace!("SSTORE key={} value={}", key, value);
 }
 
@@ -736,6 +779,60 @@ pub unsafe fn jumpdest() {
     trace!("JUMPDEST");
 }
 
+#[no_mangle]
+pub unsafe fn tload() {
+    EVM.burn_gas(100);
+    let key = EVM.stack.pop();
+    let value = EVM
+        .transient_storage
+        .as_ref()
+        .and_then(|storage| storage.get(&key).copied())
+        .unwrap_or(ZERO);
+    EVM.stack.push(value);
+    trace!("TLOAD key={}", key);
+}
+
+#[no_mangle]
+pub unsafe fn tstore() {
+    EVM.burn_gas(100);
+    let (key, value) = EVM.stack.pop2();
+    EVM.transient_storage
+        .get_or_insert_with(Default::default)
+        .insert(key, value);
+    trace!("TSTORE key={} value={}", key, value);
+}
+
+#[no_mangle]
+pub unsafe fn mcopy() {
+    let (dest_offset, offset, size) = EVM.stack.pop3();
+    // Cannot copy more than `usize::MAX` within any gas limit
+    let size = as_usize_or_oog(size);
+    EVM.burn_gas(3 + 3 * ((size as u64 + 31) / 32));
+    // TODO: gas cost for memory resize
+    if size > 0 {
+        let dest_offset = as_usize_or_oog(dest_offset);
+        let offset = as_usize_or_oog(offset);
+        // Both the source and the destination expand the memory, and may overlap
+        EVM.memory.resize(dest_offset.max(offset) + size);
+        EVM.memory
+            .bytes
+            .copy_within(offset..(offset + size), dest_offset);
+    }
+    trace!(
+        "MCOPY dest_offset={} offset={} size={}",
+        dest_offset,
+        offset,
+        size
+    );
+}
+
+#[no_mangle]
+pub unsafe fn push0() {
+    EVM.burn_gas(2);
+    EVM.stack.push(ZERO);
+    trace!("PUSH0");
+}
+
 #[no_mangle]
 pub unsafe fn push1(word: u8) {
     EVM.burn_gas(3);
@@ -1097,7 +1194,7 @@ pub unsafe fn log0() {
         topics: &[],
         data,
     };
-    ENV.log(log);
+    EVM.journal.log(log.into());
     trace!("LOG0 offset={} size={}", offset, size);
 }
 
@@ -1112,7 +1209,7 @@ pub unsafe fn log1() {
         topics: &[topic],
         data,
     };
-    ENV.log(log);
+    EVM.journal.log(log.into());
     trace!("LOG1 offset={} size={} topic={}", offset, size, topic);
 }
 
@@ -1127,7 +1224,7 @@ pub unsafe fn log2() {
         topics: &[topic1, topic2],
         data,
     };
-    ENV.log(log);
+    EVM.journal.log(log.into());
     trace!(
         "LOG2 offset={} size={} topics={{{}, {}}}",
         offset,
@@ -1148,7 +1245,7 @@ pub unsafe fn log3() {
         topics: &[topic1, topic2, topic3],
         data,
     };
-    ENV.log(log);
+    EVM.journal.log(log.into());
     trace!(
         "LOG3 offset={} size={} topics={{{}, {}, {}}}",
         offset,
@@ -1170,7 +1267,7 @@ pub unsafe fn log4() {
         topics: &[topic1, topic2, topic3, topic4],
         data,
     };
-    ENV.log(log);
+    EVM.journal.log(log.into());
     trace!(
         "LOG4 offset={} size={} topics={{{}, {}, {}, {}}}",
         offset,
@@ -1192,15 +1289,56 @@ pub unsafe fn create() {
 #[no_mangle]
 pub unsafe fn call() {
     EVM.burn_gas(100);
-    trace!("CALL");
-    todo!("CALL") // TODO
+    let (gas, address_u256, value) = EVM.stack.pop3();
+    let (args_offset, args_size, ret_offset, ret_size) = EVM.stack.pop4();
+    let address = u256_to_address(address_u256);
+    trace!("CALL address={} value={}", address_u256, value);
+    if let Some(precompile) = Precompile::from_address(&address) {
+        let success = (value == ZERO || transfer(address, value))
+            && call_precompile(
+                precompile,
+                gas,
+                args_offset,
+                args_size,
+                ret_offset,
+                ret_size,
+            );
+        EVM.stack.push(if success { ONE } else { ZERO });
+        return;
+    }
+    if args_size != ZERO {
+        todo!("CALL") // TODO: calls with call data
+    }
+    // A call without call data is a plain value transfer, as performed by
+    // `payable(to).transfer(amount)` in Solidity. There is no return data.
+    EVM.return_data.clear();
+    let success = value == ZERO || transfer(address, value);
+    EVM.stack.push(if success { ONE } else { ZERO });
 }
 
 #[no_mangle]
 pub unsafe fn callcode() {
     EVM.burn_gas(100);
-    trace!("CALLCODE");
-    todo!("CALLCODE") // TODO
+    let (gas, address_u256, value) = EVM.stack.pop3();
+    let (args_offset, args_size, ret_offset, ret_size) = EVM.stack.pop4();
+    trace!("CALLCODE address={} value={}", address_u256, value);
+    // Precompiles behave the same regardless of the calling context, and
+    // the value is "transferred" to ourselves.
+    match Precompile::from_address(&u256_to_address(address_u256)) {
+        Some(precompile) => {
+            let success = value <= current_self_balance()
+                && call_precompile(
+                    precompile,
+                    gas,
+                    args_offset,
+                    args_size,
+                    ret_offset,
+                    ret_size,
+                );
+            EVM.stack.push(if success { ONE } else { ZERO });
+        }
+        None => todo!("CALLCODE"), // TODO
+    }
 }
 
 #[no_mangle]
@@ -1208,6 +1346,7 @@ pub unsafe fn r#return() {
     EVM.burn_gas(0);
     let (offset, size) = EVM.stack.pop2();
     let data = EVM.memory.slice(offset.as_usize(), size.as_usize());
+    EVM.journal.commit(&mut ENV);
     ENV.value_return(data);
     // There is no host function to successfully terminate execution, so
     // the compiler will insert a WebAssembly RETURN instruction here.
@@ -1217,8 +1356,23 @@ pub unsafe fn r#return() {
 #[no_mangle]
 pub unsafe fn delegatecall() {
     EVM.burn_gas(100);
-    trace!("DELEGATECALL");
-    todo!("DELEGATECALL") // TODO
+    let (gas, address_u256, args_offset) = EVM.stack.pop3();
+    let (args_size, ret_offset, ret_size) = EVM.stack.pop3();
+    trace!("DELEGATECALL address={}", address_u256);
+    match Precompile::from_address(&u256_to_address(address_u256)) {
+        Some(precompile) => {
+            let success = call_precompile(
+                precompile,
+                gas,
+                args_offset,
+                args_size,
+                ret_offset,
+                ret_size,
+            );
+            EVM.stack.push(if success { ONE } else { ZERO });
+        }
+        None => todo!("DELEGATECALL"), // TODO
+    }
 }
 
 #[no_mangle]
@@ -1231,8 +1385,23 @@ pub unsafe fn create2() {
 #[no_mangle]
 pub unsafe fn staticcall() {
     EVM.burn_gas(100);
-    trace!("STATICCALL");
-    todo!("STATICCALL") // TODO
+    let (gas, address_u256, args_offset) = EVM.stack.pop3();
+    let (args_size, ret_offset, ret_size) = EVM.stack.pop3();
+    trace!("STATICCALL address={}", address_u256);
+    match Precompile::from_address(&u256_to_address(address_u256)) {
+        Some(precompile) => {
+            let success = call_precompile(
+                precompile,
+                gas,
+                args_offset,
+                args_size,
+                ret_offset,
+                ret_size,
+            );
+            EVM.stack.push(if success { ONE } else { ZERO });
+        }
+        None => todo!("STATICCALL"), // TODO
+    }
 }
 
 #[no_mangle]
@@ -1240,6 +1409,7 @@ pub unsafe fn revert() {
     EVM.burn_gas(0);
     let (offset, size) = EVM.stack.pop2();
     let data = EVM.memory.slice(offset.as_usize(), size.as_usize());
+    EVM.journal.discard();
     ENV.revert(data);
     trace!("REVERT offset={} size={}", offset, size);
 }
@@ -1249,6 +1419,7 @@ pub unsafe fn invalid() {
     // `INVALID` is "Equivalent to REVERT (since Byzantium fork) with 0,0 as stack
     // parameters, except that all the gas given to the current context is consumed."
     EVM.burn_gas(EVM.gas_limit);
+    EVM.journal.discard();
     ENV.revert(&[]);
     trace!("INVALID");
 }
@@ -1256,8 +1427,101 @@ pub unsafe fn invalid() {
 #[no_mangle]
 pub unsafe fn selfdestruct() {
     EVM.burn_gas(5000);
-    trace!("SELFDESTRUCT");
-    todo!("SELFDESTRUCT") // TODO: state reset
+    let address_u256 = EVM.stack.pop();
+    let beneficiary = u256_to_address(address_u256);
+    // Since Cancun, the whole balance is sent to the beneficiary, but the contract
+    // is only deleted if it was created in the same transaction (see EIP-6780)
+    let balance = current_self_balance();
+    if beneficiary != ENV.address() && balance != ZERO {
+        EVM.journal.transfer(beneficiary, balance);
+    }
+    EVM.journal.commit(&mut ENV);
+    if ENV.created_in_transaction() {
+        ENV.clear_storage();
+    }
+    EVM.stack.clear();
+    ENV.value_return(&[]);
+    trace!("SELFDESTRUCT beneficiary={}", address_u256);
+}
+
+/// Transfers value from the current account, failing (without reverting)
+/// if the balance is insufficient.
+unsafe fn transfer(to: Address, value: Word) -> bool {
+    EVM.burn_gas(9000);
+    if value > current_self_balance() {
+        return false;
+    }
+    EVM.journal.transfer(to, value);
+    true
+}
+
+/// The balance of an account, including the value transferred so far.
+unsafe fn current_balance(address: Address) -> Word {
+    let balance = ENV.balance(address) + EVM.journal.transferred_to(&address);
+    if address == ENV.address() {
+        balance - EVM.journal.transferred()
+    } else {
+        balance
+    }
+}
+
+/// The balance of the current account, including the value transferred so far.
+unsafe fn current_self_balance() -> Word {
+    let address = ENV.address();
+    ENV.self_balance() + EVM.journal.transferred_to(&address) - EVM.journal.transferred()
+}
+
+/// Calls a precompiled contract with the given input from memory, copying its
+/// output to memory and the return data. Returns whether the call succeeded.
+unsafe fn call_precompile(
+    precompile: Precompile,
+    gas: Word,
+    args_offset: Word,
+    args_size: Word,
+    ret_offset: Word,
+    ret_size: Word,
+) -> bool {
+    let args_size = as_usize_or_oog(args_size);
+    let input = if args_size == 0 {
+        Vec::new()
+    } else {
+        let args_offset = as_usize_or_oog(args_offset);
+        EVM.memory.resize(args_offset + args_size);
+        EVM.memory.slice(args_offset, args_size).to_vec()
+    };
+
+    // The call fails, consuming all the gas given to it, if that is not enough.
+    // It cannot be given more than all but one 64th of the gas we have left
+    // (see EIP-150), so that burning it never takes us past the gas limit.
+    let available = EVM.gas_limit.saturating_sub(EVM.gas_used);
+    let gas = u64::try_from(gas)
+        .unwrap_or(u64::MAX)
+        .min(available - available / 64);
+    let cost = precompile.gas_cost(&input);
+    let output = if cost > gas {
+        None
+    } else {
+        precompile.run::<Hasher>(&input)
+    };
+    let output = match output {
+        Some(output) => output,
+        None => {
+            EVM.burn_gas(gas);
+            EVM.return_data.clear();
+            return false;
+        }
+    };
+    EVM.burn_gas(cost);
+
+    let ret_size = as_usize_or_oog(ret_size);
+    if ret_size > 0 {
+        let ret_offset = as_usize_or_oog(ret_offset);
+        EVM.memory.resize(ret_offset + ret_size);
+        let size = ret_size.min(output.len());
+        EVM.memory.store_slice(ret_offset, &output[..size]);
+    }
+    EVM.return_data = output;
+    true
 }
 
 fn as_usize_or_oog(word: Word) -> usize {
@@ -1277,7 +1541,7 @@ fn address_to_u256(address: &Address) -> Word {
     Word::from_be_bytes(buf)
 }
 
-fn u256_to_address(word: Word) -> Address {
+pub(crate) fn u256_to_address(word: Word) -> Address {
     let mut buf = [0u8; 20];
     buf[4..20].copy_from_slice(&word.low().to_be_bytes());
     buf[0..4].copy_from_slice(&word.high().to_be_bytes()[12..16]);
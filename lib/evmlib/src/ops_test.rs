@@ -282,15 +282,14 @@ mod tests {
             .unwrap();
         let mock_balance = 132456;
         unsafe {
-            ENV.address = mock_address;
             EVM.reset();
-            EVM.self_balance = Word::from(mock_balance);
+            ENV.set_balance(mock_address, Word::from(mock_balance));
             EVM.stack
                 .push("0x2fAD5818188D71A1d6A4868d352E69f239AFdee9".hex_int());
             balance();
             assert_eq!(EVM.stack.peek(), mock_balance);
 
-            // Balances other than self are zero
+            // Unknown accounts have a zero balance
             EVM.stack
                 .push("0x0000000000000DEADBEEF0000000000000000000".hex_int());
             balance();
@@ -327,7 +326,41 @@ mod tests {
     }
 
     #[test]
-    fn test_callvalue() {}
+    fn test_callvalue() {
+        unsafe {
+            EVM.reset();
+            ENV.call_value = Word::from(42u8);
+            assert_eq!(crate::api::_evm_call_value(1), 1); // payable
+            callvalue();
+            assert_eq!(EVM.stack.peek(), 42);
+        }
+    }
+
+    #[test]
+    fn test_callvalue_not_payable() {
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.call_value = Word::from(42u8);
+            assert_eq!(crate::api::_evm_call_value(0), 0); // nonpayable
+            assert_eq!(ENV.exit_status, Some(ExitStatus::Revert));
+            assert!(ENV.return_data.is_empty());
+        }
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            assert_eq!(crate::api::_evm_call_value(0), 1); // nonpayable
+            callvalue();
+            assert_eq!(EVM.stack.peek(), 0);
+        }
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            assert_eq!(crate::api::_evm_call_value(3), 1); // view
+            callvalue();
+            assert_eq!(EVM.stack.peek(), 0);
+        }
+    }
 
     #[test]
     fn test_calldataload() {
@@ -417,19 +450,72 @@ mod tests {
     fn test_extcodecopy() {}
 
     #[test]
-    fn test_returndatasize() {}
+    fn test_returndatasize() {
+        unsafe {
+            EVM.reset();
+            returndatasize();
+            assert_eq!(EVM.stack.pop(), ZERO);
+            EVM.return_data = vec![0xAB; 3];
+            returndatasize();
+            assert_eq!(EVM.stack.pop(), 3);
+        }
+    }
 
     #[test]
-    fn test_returndatacopy() {}
+    fn test_returndatacopy() {
+        unsafe {
+            EVM.reset();
+            EVM.return_data = vec![0xAB, 0xCD, 0xEF];
+            push1(2); // size
+            push1(1); // offset
+            push1(0); // destOffset
+            returndatacopy();
+            assert_eq!(EVM.memory.slice(0, 3), &[0xCD, 0xEF, 0x00]);
+            assert_eq!(EVM.return_data.len(), 3);
+        }
+    }
 
     #[test]
     fn test_extcodehash() {}
 
     #[test]
-    fn test_blockhash() {}
+    fn test_blockhash() {
+        let block_hash = Word::from(0xB10Cu32);
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.block_height = 1000;
+            ENV.block_hashes = Some([(744, block_hash), (999, block_hash)].into());
+            for (number, expected) in [
+                (743, ZERO), // too old
+                (744, block_hash),
+                (999, block_hash),
+                (1000, ZERO), // current block
+                (1001, ZERO), // future block
+            ] {
+                EVM.stack.push(Word::from(number as u32));
+                blockhash();
+                assert_eq!(EVM.stack.pop(), expected);
+            }
+            EVM.stack.push(Word::MAX);
+            blockhash();
+            assert_eq!(EVM.stack.pop(), ZERO);
+        }
+    }
 
     #[test]
-    fn test_coinbase() {}
+    fn test_coinbase() {
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.coinbase = [0xCB; 20];
+            coinbase();
+            assert_eq!(
+                EVM.stack.pop(),
+                "0xCBCBCBCBCBCBCBCBCBCBCBCBCBCBCBCBCBCBCBCB".hex_int()
+            );
+        }
+    }
 
     #[test]
     fn test_timestamp() {
@@ -454,7 +540,15 @@ mod tests {
     }
 
     #[test]
-    fn test_difficulty() {}
+    fn test_difficulty() {
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.prev_randao = Word::MAX - 42;
+            difficulty();
+            assert_eq!(EVM.stack.pop(), Word::MAX - 42);
+        }
+    }
 
     #[test]
     fn test_gaslimit() {}
@@ -475,14 +569,42 @@ mod tests {
         let balance = 3141592653589793238;
         unsafe {
             EVM.reset();
-            EVM.self_balance = Word::from(balance);
+            ENV.set_balance(ENV.address, Word::from(balance));
             selfbalance();
             assert_eq!(EVM.stack.peek(), balance);
         }
     }
 
     #[test]
-    fn test_basefee() {}
+    fn test_basefee() {
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.base_fee = Word::from(7u8);
+            basefee();
+            assert_eq!(EVM.stack.pop(), 7);
+        }
+    }
+
+    #[test]
+    fn test_blobhash() {
+        unsafe {
+            EVM.reset();
+            push1(0);
+            blobhash();
+            assert_eq!(EVM.stack.depth, 1);
+            assert_eq!(EVM.stack.pop(), 0);
+        }
+    }
+
+    #[test]
+    fn test_blobbasefee() {
+        unsafe {
+            EVM.reset();
+            blobbasefee();
+            assert_eq!(EVM.stack.pop(), 1);
+        }
+    }
 
     #[test]
     fn test_pop() {
@@ -542,11 +664,24 @@ mod tests {
     fn test_sstore() {
         unsafe {
             EVM.reset();
+            ENV.reset();
             push1(6);
             push1(7);
             sstore();
             assert_eq!(EVM.stack.depth, 0);
+            // The write only reaches storage once the execution succeeds
+            assert_eq!(ENV.storage_read(Word::from(7u8)), 0);
+            stop();
             assert_eq!(ENV.storage_read(Word::from(7u8)), 6);
+            assert_eq!(ENV.storage.as_ref().unwrap().len(), 1);
+
+            // Zeroing a slot deletes its key
+            push1(0);
+            push1(7);
+            sstore();
+            stop();
+            assert_eq!(ENV.storage_read(Word::from(7u8)), 0);
+            assert!(ENV.storage.as_ref().unwrap().is_empty());
         }
     }
 
@@ -568,6 +703,73 @@ mod tests {
     #[test]
     fn test_jumpdest() {}
 
+    #[test]
+    fn test_tload() {
+        unsafe {
+            EVM.reset();
+            push1(42);
+            tload();
+            assert_eq!(EVM.stack.pop(), 0);
+            push1(6);
+            push1(42);
+            tstore();
+            push1(42);
+            tload();
+            assert_eq!(EVM.stack.pop(), 6);
+
+            // Transient storage does not outlive the execution
+            EVM.reset();
+            push1(42);
+            tload();
+            assert_eq!(EVM.stack.pop(), 0);
+        }
+    }
+
+    #[test]
+    fn test_tstore() {
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            push1(6);
+            push1(7);
+            tstore();
+            assert_eq!(EVM.stack.depth, 0);
+            assert_eq!(EVM.transient_storage.as_ref().unwrap()[&Word::from(7u8)], 6);
+            assert_eq!(ENV.storage_read(Word::from(7u8)), 0);
+        }
+    }
+
+    #[test]
+    fn test_mcopy() {
+        unsafe {
+            EVM.reset();
+            EVM.memory.store_slice(0, &[1, 2, 3, 4]);
+            push1(3); // size
+            push1(0); // offset
+            push1(1); // destOffset
+            mcopy();
+            assert_eq!(EVM.memory.slice(0, 4), &[1, 1, 2, 3]);
+
+            // Copying from beyond the memory expands it
+            push1(2); // size
+            push1(63); // offset
+            push1(0); // destOffset
+            mcopy();
+            assert_eq!(EVM.memory.size(), 96);
+            assert_eq!(EVM.memory.slice(0, 4), &[0, 0, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn test_push0() {
+        unsafe {
+            EVM.reset();
+            push0();
+            assert_eq!(EVM.stack.depth, 1);
+            assert_eq!(EVM.stack.slots[0], ZERO);
+        }
+    }
+
     #[test]
     fn test_push1() {
         unsafe {
@@ -963,10 +1165,11 @@ mod tests {
             push1(test_data.len() as u8);
             push1(0);
             log0();
+            stop();
             let log = ENV.logs.first().unwrap();
             assert_eq!(
                 log,
-                &crate::env::mock::OwnedEvmLog {
+                &crate::env::OwnedEvmLog {
                     address: test_address,
                     topics: Vec::new(),
                     data: test_data.to_vec()
@@ -989,10 +1192,11 @@ mod tests {
             push1(test_data.len() as u8);
             push1(0);
             log1();
+            stop();
             let log = ENV.logs.first().unwrap();
             assert_eq!(
                 log,
-                &crate::env::mock::OwnedEvmLog {
+                &crate::env::OwnedEvmLog {
                     address: test_address,
                     topics: vec![topic],
                     data: test_data.to_vec()
@@ -1017,10 +1221,11 @@ mod tests {
             push1(test_data.len() as u8);
             push1(0);
             log2();
+            stop();
             let log = ENV.logs.first().unwrap();
             assert_eq!(
                 log,
-                &crate::env::mock::OwnedEvmLog {
+                &crate::env::OwnedEvmLog {
                     address: test_address,
                     topics: vec![topic1, topic2],
                     data: test_data.to_vec()
@@ -1047,10 +1252,11 @@ mod tests {
             push1(test_data.len() as u8);
             push1(0);
             log3();
+            stop();
             let log = ENV.logs.first().unwrap();
             assert_eq!(
                 log,
-                &crate::env::mock::OwnedEvmLog {
+                &crate::env::OwnedEvmLog {
                     address: test_address,
                     topics: vec![topic1, topic2, topic3],
                     data: test_data.to_vec()
@@ -1079,10 +1285,11 @@ mod tests {
             push1(test_data.len() as u8);
             push1(0);
             log4();
+            stop();
             let log = ENV.logs.first().unwrap();
             assert_eq!(
                 log,
-                &crate::env::mock::OwnedEvmLog {
+                &crate::env::OwnedEvmLog {
                     address: test_address,
                     topics: vec![topic1, topic2, topic3, topic4],
                     data: test_data.to_vec()
@@ -1095,7 +1302,65 @@ mod tests {
     fn test_create() {}
 
     #[test]
-    fn test_call() {}
+    fn test_call() {
+        let self_address = [0x9A; 20];
+        let recipient = "0x00000000000000000000000000000000DEADBEEF".hex_int();
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.address = self_address;
+            ENV.set_balance(self_address, Word::from(100u8));
+
+            // `payable(recipient).transfer(60)`
+            for _ in 0..4 {
+                push1(0); // retSize, retOffset, argsSize, argsOffset
+            }
+            push1(60); // value
+            EVM.stack.push(recipient);
+            push2(2300); // gas
+            call();
+            assert_eq!(EVM.stack.pop(), ONE);
+            selfbalance();
+            assert_eq!(EVM.stack.pop(), 40);
+            assert!(ENV.transfers.is_empty());
+            stop();
+            assert_eq!(
+                ENV.transfers,
+                vec![(u256_to_address(recipient), Word::from(60u8))]
+            );
+            assert_eq!(ENV.balance(u256_to_address(recipient)), 60);
+            assert_eq!(ENV.self_balance(), 40);
+
+            // Transfers exceeding the balance fail without reverting
+            for _ in 0..4 {
+                push1(0);
+            }
+            push1(60);
+            EVM.stack.push(recipient);
+            push2(2300);
+            call();
+            assert_eq!(EVM.stack.pop(), ZERO);
+            assert_eq!(ENV.transfers.len(), 1);
+            assert_eq!(ENV.self_balance(), 40);
+        }
+    }
+
+    #[test]
+    fn test_account_id() {
+        unsafe {
+            ENV.reset();
+            let alice = ENV.register_account("alice.near");
+            assert_eq!(ENV.account_id(alice), "alice.near");
+
+            // Implicit accounts are not recorded, as their address is reversible
+            let implicit = ENV.register_account("0x00000000000000000000000000000000deadbeef");
+            assert_eq!(ENV.accounts.as_ref().unwrap().len(), 1);
+            assert_eq!(
+                ENV.account_id(implicit),
+                "0x00000000000000000000000000000000deadbeef"
+            );
+        }
+    }
 
     #[test]
     fn test_callcode() {}
@@ -1116,13 +1381,85 @@ mod tests {
     }
 
     #[test]
-    fn test_delegatecall() {}
+    fn test_delegatecall() {
+        unsafe {
+            EVM.reset();
+            EVM.memory.store_slice(0, &[1, 2, 3]);
+
+            // The identity precompile, with a smaller output buffer
+            push1(2); // retSize
+            push1(32); // retOffset
+            push1(3); // argsSize
+            push1(0); // argsOffset
+            push1(0x04); // address
+            push2(1000); // gas
+            delegatecall();
+            assert_eq!(EVM.stack.pop(), ONE);
+            assert_eq!(EVM.memory.slice(32, 3), &[1, 2, 0]);
+            assert_eq!(EVM.return_data, vec![1, 2, 3]);
+        }
+    }
 
     #[test]
     fn test_create2() {}
 
     #[test]
-    fn test_staticcall() {}
+    fn test_staticcall() {
+        unsafe {
+            EVM.reset();
+            EVM.memory.store_slice(0, &[0xFF]);
+
+            // `sha256(hex"FF")`
+            push1(32); // retSize
+            push1(32); // retOffset
+            push1(1); // argsSize
+            push1(0); // argsOffset
+            push1(0x02); // address
+            push2(1000); // gas
+            staticcall();
+            assert_eq!(EVM.stack.pop(), ONE);
+            assert_eq!(
+                EVM.memory.load_word(32),
+                "0xA8100AE6AA1940D0B663BB31CD466142EBBDBD5187131B92D93818987832EB89".hex_int()
+            );
+            assert_eq!(EVM.return_data.len(), 32);
+
+            // Fails without enough gas for the precompile
+            push1(32);
+            push1(32);
+            push1(1);
+            push1(0);
+            push1(0x02);
+            push1(71); // gas
+            staticcall();
+            assert_eq!(EVM.stack.pop(), ZERO);
+            assert!(EVM.return_data.is_empty());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "gas")]
+    fn test_staticcall_gas() {
+        unsafe {
+            EVM.reset();
+            push1(0); // retSize
+            push1(0); // retOffset
+            push1(0); // argsSize
+            push1(0); // argsOffset
+            push1(0x09); // blake2f, which fails without input
+            EVM.stack.push(Word::MAX); // gas
+
+            // Fails with more gas requested than we have, burning all of it
+            // but one 64th of what is left after the call itself
+            EVM.gas_limit = 10_000;
+            EVM.gas_used = 3_500;
+            staticcall();
+            assert_eq!(EVM.stack.pop(), ZERO);
+            gas();
+            assert_eq!(EVM.stack.pop(), 6_400 / 64 - 2);
+            EVM.gas_limit = 10_000_000;
+        }
+    }
 
     #[test]
     fn test_revert() {
@@ -1139,6 +1476,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_revert_state() {
+        let self_address = [0x9A; 20];
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.address = self_address;
+            ENV.set_balance(self_address, Word::from(100u8));
+
+            push1(6);
+            push1(7);
+            sstore();
+            push1(0); // size
+            push1(0); // offset
+            log0();
+            for _ in 0..4 {
+                push1(0); // retSize, retOffset, argsSize, argsOffset
+            }
+            push1(60); // value
+            push1(0xAA); // address
+            push2(2300); // gas
+            call();
+            assert_eq!(EVM.stack.pop(), ONE);
+
+            // Storage writes, logs and transfers are all discarded
+            push1(0);
+            push1(0);
+            revert();
+            assert_eq!(ENV.exit_status, Some(ExitStatus::Revert));
+            assert_eq!(ENV.storage_read(Word::from(7u8)), 0);
+            assert!(ENV.logs.is_empty());
+            assert!(ENV.transfers.is_empty());
+            assert_eq!(ENV.self_balance(), 100);
+            push1(7);
+            sload();
+            assert_eq!(EVM.stack.pop(), 0);
+        }
+    }
+
     #[test]
     fn test_invalid() {
         unsafe {
@@ -1151,7 +1527,38 @@ mod tests {
     }
 
     #[test]
-    fn test_selfdestruct() {}
+    fn test_selfdestruct() {
+        let self_address = [0x9A; 20];
+        let beneficiary = "0x00000000000000000000000000000000DEADBEEF".hex_int();
+        unsafe {
+            EVM.reset();
+            ENV.reset();
+            ENV.address = self_address;
+            ENV.set_balance(self_address, Word::from(100u8));
+            ENV.storage_write(Word::from(7u8), Word::from(6u8));
+            EVM.stack.push(beneficiary);
+            selfdestruct();
+            assert_eq!(ENV.exit_status, Some(ExitStatus::Success));
+            assert_eq!(
+                ENV.transfers,
+                vec![(u256_to_address(beneficiary), Word::from(100u8))]
+            );
+            assert_eq!(ENV.self_balance(), 0);
+            // The contract was not created in this transaction, so it is kept
+            assert_eq!(ENV.storage_read(Word::from(7u8)), 6);
+
+            EVM.reset();
+            ENV.reset();
+            ENV.address = self_address;
+            ENV.created_in_transaction = true;
+            ENV.storage_write(Word::from(7u8), Word::from(6u8));
+            EVM.stack.push(beneficiary);
+            selfdestruct();
+            assert_eq!(ENV.exit_status, Some(ExitStatus::Success));
+            assert!(ENV.transfers.is_empty());
+            assert_eq!(ENV.storage_read(Word::from(7u8)), 0);
+        }
+    }
 
     /// Helper trait to allow writing `.hex_int()` on hex strings in tests to convert
     /// them into 256-bit integers.
@@ -0,0 +1,167 @@
+//! The precompiles for the BN254 (alt_bn128) curve, used by zkSNARK verifiers
+//! (see EIP-196 and EIP-197). Field elements are encoded as 32-byte big-endian
+//! words, and points as their affine coordinates, with (0, 0) being the point at
+//! infinity. The coordinates of G2 points are encoded with the imaginary part first.
+//! Invalid points (not on the curve, or not in the subgroup) make the call fail.
+
+use super::padded_input;
+use crate::hash_provider::HashProvider;
+use bn::{AffineG1, AffineG2, Fq, Fq2, Fr, Group, G1, G2};
+
+const G1_SIZE: usize = 64;
+const G2_SIZE: usize = 128;
+const PAIR_SIZE: usize = G1_SIZE + G2_SIZE;
+
+pub const ADD_GAS_COST: u64 = 150;
+pub const MUL_GAS_COST: u64 = 6000;
+
+pub fn pairing_gas_cost(input: &[u8]) -> u64 {
+    45000 + 34000 * (input.len() / PAIR_SIZE) as u64
+}
+
+/// Adds two G1 points.
+pub fn add<H: HashProvider>(input: &[u8]) -> Option<Vec<u8>> {
+    let input = padded_input(input, 2 * G1_SIZE);
+    let a = read_g1(&input[..G1_SIZE])?;
+    let b = read_g1(&input[G1_SIZE..(2 * G1_SIZE)])?;
+    Some(write_g1(H::bn254_add(a, b)).to_vec())
+}
+
+/// Multiplies a G1 point by a scalar, which is any 32-byte word.
+pub fn mul<H: HashProvider>(input: &[u8]) -> Option<Vec<u8>> {
+    let input = padded_input(input, G1_SIZE + 32);
+    let point = read_g1(&input[..G1_SIZE])?;
+    let scalar = bn::arith::U256::from_slice(&input[G1_SIZE..(G1_SIZE + 32)]).ok()?;
+    // Since the order of G1 is the modulus of `Fr`, the scalar can be reduced first
+    let scalar = Fr::new_mul_factor(scalar);
+    Some(write_g1(H::bn254_mul(point, scalar)).to_vec())
+}
+
+/// Checks whether the product of the pairings of a list of (G1, G2) pairs is one.
+/// Returns one if so, or zero otherwise, as a 32-byte word.
+pub fn pairing<H: HashProvider>(input: &[u8]) -> Option<Vec<u8>> {
+    if input.len() % PAIR_SIZE != 0 {
+        return None;
+    }
+    let mut pairs = Vec::with_capacity(input.len() / PAIR_SIZE);
+    for pair in input.chunks(PAIR_SIZE) {
+        let g1 = read_g1(&pair[..G1_SIZE])?;
+        let g2 = read_g2(&pair[G1_SIZE..])?;
+        pairs.push((g1, g2));
+    }
+    let mut output = vec![0u8; 32];
+    output[31] = H::bn254_pairing_check(&pairs) as u8;
+    Some(output)
+}
+
+fn read_fq(bytes: &[u8]) -> Option<Fq> {
+    Fq::from_slice(bytes).ok()
+}
+
+fn read_g1(bytes: &[u8]) -> Option<G1> {
+    let x = read_fq(&bytes[0..32])?;
+    let y = read_fq(&bytes[32..64])?;
+    if x.is_zero() && y.is_zero() {
+        return Some(G1::zero());
+    }
+    AffineG1::new(x, y).ok().map(G1::from)
+}
+
+fn read_g2(bytes: &[u8]) -> Option<G2> {
+    let x = Fq2::new(read_fq(&bytes[32..64])?, read_fq(&bytes[0..32])?);
+    let y = Fq2::new(read_fq(&bytes[96..128])?, read_fq(&bytes[64..96])?);
+    if x.is_zero() && y.is_zero() {
+        return Some(G2::zero());
+    }
+    AffineG2::new(x, y).ok().map(G2::from)
+}
+
+fn write_g1(point: G1) -> [u8; G1_SIZE] {
+    let mut result = [0u8; G1_SIZE];
+    if let Some(point) = AffineG1::from_jacobian(point) {
+        // Unwraps are safe because the slices have the right length
+        point.x().to_big_endian(&mut result[0..32]).unwrap();
+        point.y().to_big_endian(&mut result[32..64]).unwrap();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_provider::Native;
+
+    const G1_GENERATOR: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+    );
+    const G1_GENERATOR_NEG: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45",
+    );
+    const G1_GENERATOR_DOUBLE: &str = concat!(
+        "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3",
+        "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4",
+    );
+    const G2_GENERATOR: &str = concat!(
+        "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2",
+        "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed",
+        "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b",
+        "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa",
+    );
+
+    #[test]
+    fn test_add() {
+        let input = hex::decode([G1_GENERATOR, G1_GENERATOR].concat()).unwrap();
+        let output = add::<Native>(&input).unwrap();
+        assert_eq!(hex::encode(output), G1_GENERATOR_DOUBLE);
+
+        // The point at infinity is the identity
+        let input = hex::decode(G1_GENERATOR).unwrap();
+        let output = add::<Native>(&input).unwrap();
+        assert_eq!(hex::encode(output), G1_GENERATOR);
+        let input = hex::decode([G1_GENERATOR, G1_GENERATOR_NEG].concat()).unwrap();
+        let output = add::<Native>(&input).unwrap();
+        assert_eq!(output, vec![0u8; 64]);
+
+        // (1, 1) is not on the curve
+        let mut input = hex::decode(G1_GENERATOR).unwrap();
+        input[63] = 1;
+        assert_eq!(add::<Native>(&input), None);
+    }
+
+    #[test]
+    fn test_mul() {
+        let scalar = "0000000000000000000000000000000000000000000000000000000000000002";
+        let input = hex::decode([G1_GENERATOR, scalar].concat()).unwrap();
+        let output = mul::<Native>(&input).unwrap();
+        assert_eq!(hex::encode(output), G1_GENERATOR_DOUBLE);
+
+        // The order of the group, plus two
+        let scalar = "30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000003";
+        let input = hex::decode([G1_GENERATOR, scalar].concat()).unwrap();
+        let output = mul::<Native>(&input).unwrap();
+        assert_eq!(hex::encode(output), G1_GENERATOR_DOUBLE);
+    }
+
+    #[test]
+    fn test_pairing() {
+        // e(G1, G2) * e(-G1, G2) == 1
+        let input =
+            hex::decode([G1_GENERATOR, G2_GENERATOR, G1_GENERATOR_NEG, G2_GENERATOR].concat())
+                .unwrap();
+        let output = pairing::<Native>(&input).unwrap();
+        assert_eq!(output[31], 1);
+        assert_eq!(pairing_gas_cost(&input), 113000);
+
+        // e(G1, G2) * e(G1, G2) != 1
+        let input =
+            hex::decode([G1_GENERATOR, G2_GENERATOR, G1_GENERATOR, G2_GENERATOR].concat()).unwrap();
+        let output = pairing::<Native>(&input).unwrap();
+        assert_eq!(output, vec![0u8; 32]);
+
+        // The empty product is one
+        assert_eq!(pairing::<Native>(&[]).unwrap()[31], 1);
+        assert_eq!(pairing::<Native>(&input[1..]), None);
+    }
+}
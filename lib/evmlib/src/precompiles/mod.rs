@@ -0,0 +1,196 @@
+//! This module contains the precompiled contracts of the EVM, which are called
+//! like any other contract, but are implemented natively. Where NEAR provides a
+//! host function for the underlying primitive, it is used through `HashProvider`.
+
+use crate::{env::Address, hash_provider::HashProvider, state::Word};
+
+mod blake2f;
+mod bn254;
+mod modexp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precompile {
+    EcRecover = 0x01,
+    Sha256 = 0x02,
+    Ripemd160 = 0x03,
+    Identity = 0x04,
+    ModExp = 0x05,
+    Bn254Add = 0x06,
+    Bn254Mul = 0x07,
+    Bn254Pairing = 0x08,
+    Blake2F = 0x09,
+}
+
+impl Precompile {
+    /// Returns the precompile at the given address, if any.
+    pub fn from_address(address: &Address) -> Option<Self> {
+        if address[..19].iter().any(|b| *b != 0) {
+            return None;
+        }
+        match address[19] {
+            0x01 => Some(Self::EcRecover),
+            0x02 => Some(Self::Sha256),
+            0x03 => Some(Self::Ripemd160),
+            0x04 => Some(Self::Identity),
+            0x05 => Some(Self::ModExp),
+            0x06 => Some(Self::Bn254Add),
+            0x07 => Some(Self::Bn254Mul),
+            0x08 => Some(Self::Bn254Pairing),
+            0x09 => Some(Self::Blake2F),
+            _ => None,
+        }
+    }
+
+    /// The gas cost of running the precompile on the given input.
+    pub fn gas_cost(self, input: &[u8]) -> u64 {
+        let words = (input.len() as u64 + 31) / 32;
+        match self {
+            Self::EcRecover => 3000,
+            Self::Sha256 => 60 + 12 * words,
+            Self::Ripemd160 => 600 + 120 * words,
+            Self::Identity => 15 + 3 * words,
+            Self::ModExp => modexp::gas_cost(input),
+            Self::Bn254Add => bn254::ADD_GAS_COST,
+            Self::Bn254Mul => bn254::MUL_GAS_COST,
+            Self::Bn254Pairing => bn254::pairing_gas_cost(input),
+            Self::Blake2F => blake2f::gas_cost(input),
+        }
+    }
+
+    /// Runs the precompile on the given input, returning its output, or `None` if
+    /// the input is malformed, which makes the call fail and consume all its gas.
+    /// The caller is responsible for charging the gas cost first.
+    pub fn run<H: HashProvider>(self, input: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::EcRecover => Some(ecrecover::<H>(input)),
+            Self::Sha256 => Some(H::sha256(input).to_vec()),
+            Self::Ripemd160 => {
+                let mut output = vec![0u8; 32];
+                output[12..].copy_from_slice(&H::ripemd160(input));
+                Some(output)
+            }
+            Self::Identity => Some(input.to_vec()),
+            Self::ModExp => modexp::run(input),
+            Self::Bn254Add => bn254::add::<H>(input),
+            Self::Bn254Mul => bn254::mul::<H>(input),
+            Self::Bn254Pairing => bn254::pairing::<H>(input),
+            Self::Blake2F => blake2f::run(input),
+        }
+    }
+}
+
+/// Returns the input, padded with zeros to (at least) the given length,
+/// since precompiles treat any missing input as zeros.
+fn padded_input(input: &[u8], len: usize) -> std::borrow::Cow<[u8]> {
+    if input.len() >= len {
+        input.into()
+    } else {
+        let mut padded = input.to_vec();
+        padded.resize(len, 0);
+        padded.into()
+    }
+}
+
+/// Recovers the address that signed a hash. The input consists of the hash,
+/// `v` (27 or 28), `r` and `s`, each as a 32-byte word. Returns the address as
+/// a 32-byte word, or no output at all if the signature is invalid.
+fn ecrecover<H: HashProvider>(input: &[u8]) -> Vec<u8> {
+    let input = padded_input(input, 128);
+    let hash: [u8; 32] = input[0..32].try_into().unwrap();
+    let v = Word::from_be_bytes(input[32..64].try_into().unwrap());
+    let signature: [u8; 64] = input[64..128].try_into().unwrap();
+    let recovery_id = match u8::try_from(v) {
+        Ok(v @ (27 | 28)) => v - 27,
+        _ => return Vec::new(),
+    };
+    match H::ecrecover(&hash, &signature, recovery_id) {
+        Some(public_key) => {
+            let mut output = H::keccak256(&public_key).to_vec();
+            output[..12].fill(0);
+            output
+        }
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_provider::Native;
+
+    #[test]
+    fn test_from_address() {
+        let mut address = [0u8; 20];
+        assert_eq!(Precompile::from_address(&address), None);
+        address[19] = 1;
+        assert_eq!(
+            Precompile::from_address(&address),
+            Some(Precompile::EcRecover)
+        );
+        address[19] = 9;
+        assert_eq!(
+            Precompile::from_address(&address),
+            Some(Precompile::Blake2F)
+        );
+        address[19] = 10;
+        assert_eq!(Precompile::from_address(&address), None);
+        address[19] = 1;
+        address[0] = 1;
+        assert_eq!(Precompile::from_address(&address), None);
+    }
+
+    #[test]
+    fn test_ecrecover() {
+        // Test case from https://www.evm.codes/precompiled
+        let input = hex::decode(concat!(
+            "456e9aea5e197a1f1af7a3e85a3212fa4049a3ba34c2289b4c860fc0b0c64ef3",
+            "000000000000000000000000000000000000000000000000000000000000001c",
+            "9242685bf161793cc25603c231bc2f568eb630ea16aa137d2664ac8038825608",
+            "4f8ae3bd7535248d0bd448298cc2e2071e56992d0774dc340c368ae950852ada",
+        ))
+        .unwrap();
+        let output = Precompile::EcRecover.run::<Native>(&input).unwrap();
+        assert_eq!(
+            hex::encode(output),
+            "0000000000000000000000007156526fbd7a3c72969b54f64e42c10fbb768c8a"
+        );
+        assert_eq!(Precompile::EcRecover.gas_cost(&input), 3000);
+
+        // Invalid `v` values produce no output
+        let mut input = input;
+        input[63] = 29;
+        assert!(Precompile::EcRecover
+            .run::<Native>(&input)
+            .unwrap()
+            .is_empty());
+        assert!(Precompile::EcRecover.run::<Native>(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sha256() {
+        let output = Precompile::Sha256.run::<Native>(&[0xFF]).unwrap();
+        assert_eq!(
+            hex::encode(output),
+            "a8100ae6aa1940d0b663bb31cd466142ebbdbd5187131b92d93818987832eb89"
+        );
+        assert_eq!(Precompile::Sha256.gas_cost(&[0xFF]), 72);
+        assert_eq!(Precompile::Sha256.gas_cost(&[]), 60);
+    }
+
+    #[test]
+    fn test_ripemd160() {
+        let output = Precompile::Ripemd160.run::<Native>(&[0xFF]).unwrap();
+        assert_eq!(
+            hex::encode(output),
+            "0000000000000000000000002c0c45d3ecab80fe060e5f1d7057cd2f8de5e557"
+        );
+        assert_eq!(Precompile::Ripemd160.gas_cost(&[0xFF]), 720);
+    }
+
+    #[test]
+    fn test_identity() {
+        let output = Precompile::Identity.run::<Native>(&[1, 2, 3]).unwrap();
+        assert_eq!(output, vec![1, 2, 3]);
+        assert_eq!(Precompile::Identity.gas_cost(&[0u8; 33]), 21);
+    }
+}
@@ -0,0 +1,118 @@
+//! The modular exponentiation precompile (see EIP-198), with the gas cost of EIP-2565.
+//! The input consists of the lengths of the base, exponent and modulus, each as a
+//! 32-byte word, followed by the base, exponent and modulus themselves.
+
+use super::padded_input;
+use crate::state::Word;
+
+const MIN_GAS_COST: u64 = 200;
+
+pub fn gas_cost(input: &[u8]) -> u64 {
+    // Lengths this large could never be paid for within any gas limit
+    let (base_len, exp_len, mod_len) = match lengths(input) {
+        Some(lengths) => lengths,
+        None => return u64::MAX,
+    };
+
+    let words = (base_len.max(mod_len) as u128 + 7) / 8;
+    let multiplication_complexity = words * words;
+
+    // Only the first 32 bytes of the exponent matter for the iteration count
+    let exp_head_len = exp_len.min(32);
+    let exp_head_offset = usize::try_from(base_len)
+        .ok()
+        .and_then(|base_len| base_len.checked_add(96))
+        .unwrap_or(usize::MAX);
+    let exp_head = read(input, exp_head_offset, exp_head_len as usize);
+    let mut exp_head_word = [0u8; 32];
+    exp_head_word[(32 - exp_head.len())..].copy_from_slice(&exp_head);
+    let exp_head_bits = 256 - Word::from_be_bytes(exp_head_word).leading_zeros() as u128;
+    let iteration_count = 8 * (exp_len - exp_head_len) as u128 + exp_head_bits.saturating_sub(1);
+
+    let gas = multiplication_complexity.saturating_mul(iteration_count.max(1)) / 3;
+    u64::try_from(gas).unwrap_or(u64::MAX).max(MIN_GAS_COST)
+}
+
+/// Returns `(base ^ exponent) % modulus`, padded to the length of the modulus,
+/// or `None` if the lengths do not even fit in memory.
+pub fn run(input: &[u8]) -> Option<Vec<u8>> {
+    let (base_len, exp_len, mod_len) = lengths(input)?;
+    let base_len = usize::try_from(base_len).ok()?;
+    let exp_len = usize::try_from(exp_len).ok()?;
+    let mod_len = usize::try_from(mod_len).ok()?;
+    if base_len == 0 && mod_len == 0 {
+        return Some(Vec::new());
+    }
+
+    let base = read(input, 96, base_len);
+    let exp = read(input, 96usize.saturating_add(base_len), exp_len);
+    let modulus = read(
+        input,
+        96usize.saturating_add(base_len).saturating_add(exp_len),
+        mod_len,
+    );
+    let result = aurora_engine_modexp::modexp(&base, &exp, &modulus);
+
+    let mut output = vec![0u8; mod_len];
+    // The result is smaller than the modulus, apart from any leading zeros
+    let result = &result[result.len().saturating_sub(mod_len)..];
+    output[(mod_len - result.len())..].copy_from_slice(result);
+    Some(output)
+}
+
+/// Returns the lengths of the base, exponent and modulus, unless they exceed `u64::MAX`.
+fn lengths(input: &[u8]) -> Option<(u64, u64, u64)> {
+    let header = padded_input(input, 96);
+    let length = |i: usize| {
+        let word = Word::from_be_bytes(header[(32 * i)..(32 * (i + 1))].try_into().unwrap());
+        u64::try_from(word).ok()
+    };
+    Some((length(0)?, length(1)?, length(2)?))
+}
+
+/// Reads the given range of the input, treating any missing input as zeros.
+fn read(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut result = vec![0u8; len];
+    if offset < input.len() {
+        let available = &input[offset..];
+        let len = available.len().min(len);
+        result[..len].copy_from_slice(&available[..len]);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modexp() {
+        // 3 ^ (2^256 - 2^32 - 978) % (2^256 - 2^32 - 977), from EIP-198
+        let input = hex::decode(concat!(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "03",
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+        ))
+        .unwrap();
+        assert_eq!(
+            hex::encode(run(&input).unwrap()),
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(gas_cost(&input), 1360);
+
+        // The modulus is implicitly zero here, as the input is truncated
+        let input = &input[..(96 + 1 + 32)];
+        assert_eq!(run(input).unwrap(), vec![0u8; 32]);
+
+        // The exponent length is too large to ever be paid for
+        let mut input = vec![0u8; 96];
+        input[32] = 0x01;
+        assert_eq!(gas_cost(&input), u64::MAX);
+        assert_eq!(run(&input), None);
+        assert_eq!(gas_cost(&[]), MIN_GAS_COST);
+        assert_eq!(run(&[]), Some(Vec::new()));
+    }
+}
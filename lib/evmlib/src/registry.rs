@@ -0,0 +1,124 @@
+//! This module maps NEAR account IDs to EVM addresses and back.
+//!
+//! Ethereum-compatible implicit accounts (`0x` followed by 40 lowercase hex digits)
+//! simply spell out their address, so they map both ways without hashing.
+//! NEAR implicit accounts (64 lowercase hex digits, an ED25519 public key) are
+//! not hashed either, keeping the last 20 bytes of the key.
+//! Any other account ID is hashed with Keccak-256, keeping the last 20 bytes.
+//! Only the first kind can be reversed, so the `Env` implementations record the
+//! address of each other account in a registry when they first see it, so that
+//! it can be mapped back later.
+
+use crate::{env::Address, hash_provider::HashProvider};
+
+const IMPLICIT_PREFIX: &[u8] = b"0x";
+const IMPLICIT_LEN: usize = IMPLICIT_PREFIX.len() + 2 * 20;
+const NEAR_IMPLICIT_LEN: usize = 2 * 32;
+
+/// Derives the EVM address of a NEAR account.
+pub fn account_id_to_address<H: HashProvider>(account_id: &[u8]) -> Address {
+    if let Some(address) = implicit_address(account_id) {
+        return address;
+    }
+    if let Some(address) = near_implicit_address(account_id) {
+        return address;
+    }
+    let hash = H::keccak256(account_id);
+    let mut result = [0u8; 20];
+    result.copy_from_slice(&hash[12..32]);
+    result
+}
+
+/// Returns the address spelled out by an Ethereum-compatible implicit account.
+pub fn implicit_address(account_id: &[u8]) -> Option<Address> {
+    if account_id.len() != IMPLICIT_LEN || !account_id.starts_with(IMPLICIT_PREFIX) {
+        return None;
+    }
+    let digits = &account_id[IMPLICIT_PREFIX.len()..];
+    // NEAR account IDs are always lowercase
+    if digits.iter().any(u8::is_ascii_uppercase) {
+        return None;
+    }
+    hex::decode(digits).ok()?.try_into().ok()
+}
+
+/// Returns the address of a NEAR implicit account, which is the last 20 bytes
+/// of its public key.
+pub fn near_implicit_address(account_id: &[u8]) -> Option<Address> {
+    if account_id.len() != NEAR_IMPLICIT_LEN || account_id.iter().any(u8::is_ascii_uppercase) {
+        return None;
+    }
+    let public_key = hex::decode(account_id).ok()?;
+    public_key[12..32].try_into().ok()
+}
+
+/// Returns the Ethereum-compatible implicit account of an address.
+pub fn implicit_account_id(address: &Address) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_provider::Native;
+
+    #[test]
+    fn test_implicit_account() {
+        let account_id = b"0x2fad5818188d71a1d6a4868d352e69f239afdee9";
+        let address = account_id_to_address::<Native>(account_id);
+        assert_eq!(
+            address.to_vec(),
+            hex::decode("2fad5818188d71a1d6a4868d352e69f239afdee9").unwrap()
+        );
+        assert_eq!(implicit_address(account_id), Some(address));
+        assert_eq!(implicit_account_id(&address).as_bytes(), account_id);
+    }
+
+    #[test]
+    fn test_near_implicit_account() {
+        let account_id = b"98793cd91a3f870fb126f66285808c7e094afcfc4eda8a970f6648cdf0dbd6de";
+        let address = account_id_to_address::<Native>(account_id);
+        assert_eq!(
+            address.to_vec(),
+            hex::decode("85808c7e094afcfc4eda8a970f6648cdf0dbd6de").unwrap()
+        );
+        assert_eq!(near_implicit_address(account_id), Some(address));
+        assert_eq!(implicit_address(account_id), None);
+
+        // Not valid implicit accounts, so these are hashed
+        let account_id = b"98793CD91A3F870FB126F66285808C7E094AFCFC4EDA8A970F6648CDF0DBD6DE";
+        assert_eq!(near_implicit_address(account_id), None);
+        assert_eq!(
+            account_id_to_address::<Native>(account_id),
+            Native::keccak256(account_id)[12..32]
+        );
+        assert_eq!(
+            near_implicit_address(
+                b"98793cd91a3f870fb126f66285808c7e094afcfc4eda8a970f6648cdf0dbd6dx"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_named_account() {
+        let account_id = b"alice.near";
+        let address = account_id_to_address::<Native>(account_id);
+        assert_eq!(&address, &Native::keccak256(account_id)[12..32]);
+        assert_eq!(implicit_address(account_id), None);
+
+        // Not valid implicit accounts, so these are hashed as well
+        assert_eq!(
+            implicit_address(b"0x2FAD5818188D71A1D6A4868D352E69F239AFDEE9"),
+            None
+        );
+        assert_eq!(
+            implicit_address(b"0x2fad5818188d71a1d6a4868d352e69f239afdee"),
+            None
+        );
+        assert_eq!(
+            implicit_address(b"98793cd91a3f870fb126f66285808c7e094afcfc4eda8a970f6648cdf0dbd6de"),
+            None
+        );
+    }
+}
@@ -1,7 +1,8 @@
 // This is free and unencumbered software released into the public domain.
 
+use crate::journal::Journal;
 use ethnum::u256;
-use std::mem;
+use std::{collections::HashMap, mem};
 
 pub(crate) type Word = u256;
 
@@ -34,11 +35,13 @@ pub(crate) struct Machine {
     pub(crate) call_value: Word,
     pub(crate) code: Vec<u8>,
     pub(crate) chain_id: Word,
-    // TODO: balance should be determined from context, but it is
-    // unclear how this should work from a design perspective.
-    // Should the "balance" mean the native platform token (NEAR in this case)?
-    // Or should it really mean ETH (which would require some interaction with Aurora)?
-    pub(crate) self_balance: Word,
+    /// The output of the most recent call
+    pub(crate) return_data: Vec<u8>,
+    /// The transient storage (see EIP-1153), which lasts for a single execution
+    /// and is never persisted
+    pub(crate) transient_storage: Option<HashMap<Word, Word>>,
+    /// The changes to the contract state, which are only applied on success
+    pub(crate) journal: Journal,
 }
 
 impl Stack {
@@ -202,9 +205,13 @@ impl Memory {
 impl Machine {
     #[allow(dead_code)]
     pub fn reset(&mut self) {
+        self.gas_used = 0;
         self.stack.clear();
         self.memory.clear();
         self.code.clear();
+        self.return_data.clear();
+        self.transient_storage = None;
+        self.journal.discard();
     }
 
     #[inline]
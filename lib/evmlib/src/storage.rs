@@ -0,0 +1,338 @@
+//! This module defines how the contract state is laid out in the key-value store
+//! of the environment. Each EVM storage slot is stored under a key starting with
+//! the version byte of its layout, so that different layouts never collide:
+//!
+//! - `V1` keys hold the full 32-byte slot key.
+//! - `V2` keys strip the leading zero bytes of the slot key. This saves NEAR storage
+//!   staking, since Solidity places most state variables in low-numbered slots.
+//!
+//! The layout in use is recorded under its own key. Contracts without such a record
+//! use `V1`, which predates it, until they are migrated to the latest layout. While
+//! a migration is in progress, slots are looked up in both layouts, and are always
+//! written to the latest one.
+
+use crate::env::Address;
+use crate::state::{Word, WORD_SIZE, ZERO};
+
+const V1_KEY_SIZE: usize = 1 + WORD_SIZE;
+const V1_VERSION_BYTE: u8 = 1;
+const V2_VERSION_BYTE: u8 = 2;
+
+const ACCOUNT_KEY_SIZE: usize = 1 + 20;
+// Kept far apart from the version bytes of EVM storage slots.
+const ACCOUNT_PREFIX_BYTE: u8 = 0xFF;
+const LAYOUT_KEY: [u8; 1] = [0xFE];
+const BLOCK_SEED_PREFIX_BYTE: u8 = 0xFD;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageVersion {
+    V1,
+    V2,
+}
+
+impl StorageVersion {
+    /// The layout that contracts are migrated to
+    pub const LATEST: Self = Self::V2;
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            V1_VERSION_BYTE => Some(Self::V1),
+            V2_VERSION_BYTE => Some(Self::V2),
+            _ => None,
+        }
+    }
+
+    fn as_byte(self) -> u8 {
+        match self {
+            Self::V1 => V1_VERSION_BYTE,
+            Self::V2 => V2_VERSION_BYTE,
+        }
+    }
+}
+
+pub enum StorageKey {
+    V1([u8; V1_KEY_SIZE]),
+    /// Only the given number of bytes are part of the key
+    V2([u8; V1_KEY_SIZE], usize),
+    /// The address registry entry holding the account ID of an address
+    Account([u8; ACCOUNT_KEY_SIZE]),
+    /// The record of the storage layout in use
+    Layout,
+    /// The random seed recorded for the latest block height ending in the given byte
+    BlockSeed([u8; 2]),
+}
+
+impl StorageKey {
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::V1(bytes) => bytes,
+            Self::V2(bytes, len) => &bytes[..*len],
+            Self::Account(bytes) => bytes,
+            Self::Layout => &LAYOUT_KEY,
+            Self::BlockSeed(bytes) => bytes,
+        }
+    }
+
+    pub fn from_word(word: Word, version: StorageVersion) -> Self {
+        let mut bytes = [0u8; V1_KEY_SIZE];
+        bytes[0] = version.as_byte();
+        match version {
+            StorageVersion::V1 => {
+                bytes[1..].copy_from_slice(&word.to_be_bytes());
+                Self::V1(bytes)
+            }
+            StorageVersion::V2 => {
+                let word_len = WORD_SIZE - (word.leading_zeros() / 8) as usize;
+                let word_bytes = word.to_be_bytes();
+                bytes[1..=word_len].copy_from_slice(&word_bytes[(WORD_SIZE - word_len)..]);
+                Self::V2(bytes, 1 + word_len)
+            }
+        }
+    }
+
+    pub fn from_address(address: &Address) -> Self {
+        let mut bytes = [0u8; ACCOUNT_KEY_SIZE];
+        bytes[0] = ACCOUNT_PREFIX_BYTE;
+        bytes[1..].copy_from_slice(address);
+        Self::Account(bytes)
+    }
+
+    pub fn from_block_height(height: u64) -> Self {
+        Self::BlockSeed([BLOCK_SEED_PREFIX_BYTE, height as u8])
+    }
+
+    /// Whether the given raw key holds an EVM storage slot, in any layout
+    pub fn is_slot(key: &[u8]) -> bool {
+        key.first()
+            .and_then(|byte| StorageVersion::from_byte(*byte))
+            .is_some()
+    }
+}
+
+impl AsRef<[u8]> for StorageKey {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+/// Raw access to the key-value store holding the contract state.
+pub trait RawStorage {
+    fn raw_read(&mut self, key: &[u8]) -> Option<Vec<u8>>;
+    fn raw_write(&mut self, key: &[u8], value: &[u8]);
+    /// Returns whether the key was present
+    fn raw_remove(&mut self, key: &[u8]) -> bool;
+}
+
+/// The storage layout of a contract, which may be in the middle of a migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub version: StorageVersion,
+    /// The layout being migrated from, which may still hold some slots
+    pub legacy: Option<StorageVersion>,
+}
+
+impl Layout {
+    pub fn load<S: RawStorage>(storage: &mut S) -> Self {
+        let record = storage
+            .raw_read(StorageKey::Layout.as_slice())
+            .unwrap_or_default();
+        let version = |byte: &u8| StorageVersion::from_byte(*byte).expect("invalid storage layout");
+        match record.as_slice() {
+            [] => Self {
+                version: StorageVersion::V1,
+                legacy: None,
+            },
+            [current] => Self {
+                version: version(current),
+                legacy: None,
+            },
+            [current, legacy] => Self {
+                version: version(current),
+                legacy: Some(version(legacy)),
+            },
+            _ => panic!("invalid storage layout"),
+        }
+    }
+
+    fn save<S: RawStorage>(&self, storage: &mut S) {
+        match self.legacy {
+            None => storage.raw_write(StorageKey::Layout.as_slice(), &[self.version.as_byte()]),
+            Some(legacy) => {
+                let record = [self.version.as_byte(), legacy.as_byte()];
+                storage.raw_write(StorageKey::Layout.as_slice(), &record)
+            }
+        }
+    }
+
+    pub fn read_slot<S: RawStorage>(&self, storage: &mut S, key: Word) -> Word {
+        let value = storage
+            .raw_read(StorageKey::from_word(key, self.version).as_slice())
+            .or_else(|| {
+                let legacy = self.legacy?;
+                storage.raw_read(StorageKey::from_word(key, legacy).as_slice())
+            });
+        value
+            .map(|bytes| Word::from_be_bytes(bytes.try_into().unwrap()))
+            .unwrap_or(ZERO)
+    }
+
+    /// Writes a slot, or deletes it if the value is zero, since missing slots
+    /// read as zero anyway, and deleting them releases their storage staking.
+    pub fn write_slot<S: RawStorage>(&self, storage: &mut S, key: Word, value: Word) {
+        if value == ZERO {
+            return self.remove_slot(storage, key);
+        }
+        let storage_key = StorageKey::from_word(key, self.version);
+        storage.raw_write(storage_key.as_slice(), &value.to_be_bytes());
+        if let Some(legacy) = self.legacy {
+            storage.raw_remove(StorageKey::from_word(key, legacy).as_slice());
+        }
+    }
+
+    pub fn remove_slot<S: RawStorage>(&self, storage: &mut S, key: Word) {
+        storage.raw_remove(StorageKey::from_word(key, self.version).as_slice());
+        if let Some(legacy) = self.legacy {
+            storage.raw_remove(StorageKey::from_word(key, legacy).as_slice());
+        }
+    }
+}
+
+/// Moves the given slots to the latest layout, starting a migration if needed,
+/// and returns the number of slots moved. Completing the migration stops looking
+/// up slots in the old layout, so it must only be done once all of them have
+/// been moved. Slots are given by their EVM keys, since the contract cannot
+/// enumerate its own storage.
+pub fn migrate<S: RawStorage>(storage: &mut S, slots: &[Word], complete: bool) -> usize {
+    let mut layout = Layout::load(storage);
+    if layout.version != StorageVersion::LATEST {
+        layout = Layout {
+            version: StorageVersion::LATEST,
+            legacy: Some(layout.version),
+        };
+    }
+
+    let mut moved = 0;
+    if let Some(legacy) = layout.legacy {
+        for slot in slots {
+            let legacy_key = StorageKey::from_word(*slot, legacy);
+            if let Some(value) = storage.raw_read(legacy_key.as_slice()) {
+                storage.raw_remove(legacy_key.as_slice());
+                // A slot found in both layouts was written since the migration started
+                let key = StorageKey::from_word(*slot, layout.version);
+                if storage.raw_read(key.as_slice()).is_none() {
+                    storage.raw_write(key.as_slice(), &value);
+                }
+                moved += 1;
+            }
+        }
+    }
+
+    if complete {
+        layout.legacy = None;
+    }
+    layout.save(storage);
+    moved
+}
+
+/// Records the random seed of the block at the given height, in place of the
+/// seed of the block 256 heights before it, so that `block_seed` can find it
+/// in the following blocks.
+pub fn record_block_seed<S: RawStorage>(storage: &mut S, height: u64, seed: &[u8; 32]) {
+    let key = StorageKey::from_block_height(height);
+    let mut record = [0u8; 8 + 32];
+    record[..8].copy_from_slice(&height.to_be_bytes());
+    record[8..].copy_from_slice(seed);
+    if storage.raw_read(key.as_slice()).as_deref() != Some(&record[..]) {
+        storage.raw_write(key.as_slice(), &record);
+    }
+}
+
+/// The random seed recorded for the block at the given height, if any.
+pub fn block_seed<S: RawStorage>(storage: &mut S, height: u64) -> Option<[u8; 32]> {
+    let record = storage.raw_read(StorageKey::from_block_height(height).as_slice())?;
+    let (recorded_height, seed) = record.split_at(8);
+    if recorded_height != height.to_be_bytes() {
+        return None;
+    }
+    seed.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{mock::MockEnv, Env};
+
+    #[test]
+    fn test_storage_key() {
+        let key = StorageKey::from_word(Word::from(0x0102u16), StorageVersion::V1);
+        assert_eq!(key.as_slice().len(), 33);
+        assert_eq!(key.as_slice()[0], 1);
+        assert_eq!(&key.as_slice()[31..], &[0x01, 0x02]);
+
+        let key = StorageKey::from_word(Word::from(0x0102u16), StorageVersion::V2);
+        assert_eq!(key.as_slice(), &[2, 0x01, 0x02]);
+        let key = StorageKey::from_word(ZERO, StorageVersion::V2);
+        assert_eq!(key.as_slice(), &[2]);
+        let key = StorageKey::from_word(Word::MAX, StorageVersion::V2);
+        assert_eq!(key.as_slice().len(), 33);
+
+        assert!(StorageKey::is_slot(&[2, 0x01]));
+        assert!(!StorageKey::is_slot(&LAYOUT_KEY));
+        assert!(!StorageKey::is_slot(
+            StorageKey::from_address(&[0u8; 20]).as_slice()
+        ));
+        assert!(!StorageKey::is_slot(
+            StorageKey::from_block_height(0x0102).as_slice()
+        ));
+    }
+
+    #[test]
+    fn test_block_seed() {
+        let mut env = MockEnv::default();
+        assert_eq!(block_seed(&mut env, 7), None);
+        record_block_seed(&mut env, 7, &[1u8; 32]);
+        record_block_seed(&mut env, 7, &[1u8; 32]);
+        assert_eq!(block_seed(&mut env, 7), Some([1u8; 32]));
+        assert_eq!(block_seed(&mut env, 7 + 256), None);
+
+        // The seed of a later height ending in the same byte takes its place
+        record_block_seed(&mut env, 7 + 256, &[2u8; 32]);
+        assert_eq!(block_seed(&mut env, 7), None);
+        assert_eq!(block_seed(&mut env, 7 + 256), Some([2u8; 32]));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut env = MockEnv::default();
+        let slots = [ZERO, Word::from(7u8), Word::MAX];
+        for (i, slot) in slots.iter().enumerate() {
+            env.storage_write(*slot, Word::from(i as u8 + 1));
+        }
+        let v1_key = StorageKey::from_word(Word::from(7u8), StorageVersion::V1);
+        assert!(env.raw_read(v1_key.as_slice()).is_some());
+
+        // Slots are found in either layout during the migration
+        assert_eq!(migrate(&mut env, &slots[..1], false), 1);
+        assert_eq!(Layout::load(&mut env).legacy, Some(StorageVersion::V1));
+        env.storage_write(Word::MAX, Word::from(42u8));
+        for (slot, value) in [(ZERO, 1u8), (Word::from(7u8), 2), (Word::MAX, 42)] {
+            assert_eq!(env.storage_read(slot), Word::from(value));
+        }
+
+        assert_eq!(migrate(&mut env, &slots, true), 1);
+        assert!(env.raw_read(v1_key.as_slice()).is_none());
+        let v2_key = StorageKey::from_word(Word::from(7u8), StorageVersion::V2);
+        assert!(env.raw_read(v2_key.as_slice()).is_some());
+        assert_eq!(
+            Layout::load(&mut env),
+            Layout {
+                version: StorageVersion::V2,
+                legacy: None,
+            }
+        );
+        for (slot, value) in [(ZERO, 1u8), (Word::from(7u8), 2), (Word::MAX, 42)] {
+            assert_eq!(env.storage_read(slot), Word::from(value));
+        }
+        assert_eq!(migrate(&mut env, &slots, true), 0);
+    }
+}
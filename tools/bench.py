@@ -10,10 +10,6 @@ contracts = list(map(lambda x: x[:-5], os.listdir("tools/benchmark/inputs")))
 print(f"contracts = {contracts}")
 
 
-def compile(name: str):
-    os.system(f'./evm2near test/{name}.sol -o {name}.wasm -b near')
-
-
 def copy(name: str):
     os.system(f'cp {name}.wasm tools/benchmark/{name}.wasm')
 
@@ -23,8 +19,8 @@ def remove(name: str):
 
 
 def compile_contracts():
-    for contract in contracts:
-        compile(contract)
+    inputs = ' '.join(f'test/{name}.sol' for name in contracts)
+    os.system(f'./evm2near {inputs} --out-dir . -b near')
 
 
 def copy_contracts():